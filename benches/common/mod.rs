@@ -0,0 +1,49 @@
+use jj_lib::backend::CommitId;
+use jj_lib::config::StackedConfig;
+use jj_lib::repo::Repo as _;
+use jj_lib::settings::UserSettings;
+use jj_lib::workspace::Workspace;
+use std::path::PathBuf;
+
+/// A repo with `commits` empty commits, created once in the temp dir and reused by later runs.
+///
+/// The history is a mainline with a side branch forking off every 50 commits and being merged back,
+/// so the graph has a few lanes like a real project.
+pub fn synthetic_repo(commits: usize) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("kahva-bench-{commits}"));
+    if dir.join(".jj").join("repo").exists() {
+        return dir;
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let settings = UserSettings::from_config(StackedConfig::with_defaults()).unwrap();
+    let (_workspace, repo) = Workspace::init_simple(&settings, &dir).unwrap();
+    let empty_tree = repo.store().empty_merged_tree_id();
+
+    let mut tx = repo.start_transaction();
+    let mut mainline = repo.store().root_commit_id().clone();
+    let mut side: Option<CommitId> = None;
+    for i in 0..commits {
+        let (parents, on_side) = match side.take() {
+            Some(side_head) if i % 50 == 49 => (vec![mainline.clone(), side_head], false),
+            side_head if i % 10 == 3 => (vec![side_head.unwrap_or_else(|| mainline.clone())], true),
+            side_head => {
+                side = side_head;
+                (vec![mainline.clone()], false)
+            }
+        };
+        let commit = tx
+            .repo_mut()
+            .new_commit(parents, empty_tree.clone())
+            .set_description(format!("commit {i}\n\nbody of commit {i}\n"))
+            .write()
+            .unwrap();
+        match on_side {
+            true => side = Some(commit.id().clone()),
+            false => mainline = commit.id().clone(),
+        }
+    }
+    tx.commit("create synthetic history").unwrap();
+    dir
+}
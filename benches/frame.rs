@@ -0,0 +1,38 @@
+mod common;
+
+use clap::Parser;
+use criterion::{Criterion, criterion_group, criterion_main};
+use eframe::egui;
+use kahva::{App, Args};
+use std::time::Duration;
+
+/// Draws frames of the fully loaded log of a repo with 10k commits.
+fn frame(c: &mut Criterion) {
+    let dir = common::synthetic_repo(10_000);
+    let repository = dir.to_str().unwrap();
+    let args = Args::parse_from(["kahva", "--repository", repository, "-r", "all()", "--ignore-working-copy"]);
+
+    let ctx = egui::Context::default();
+    let raw_input = || egui::RawInput {
+        screen_rect: Some(egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(1200.0, 800.0))),
+        ..Default::default()
+    };
+    let mut app = App::load(args).unwrap();
+    app.init(&ctx, None).unwrap();
+    app.load_all();
+    // the first frames send the reload, wait until the view arrived
+    loop {
+        let _ = ctx.run(raw_input(), |ctx| app.frame(ctx));
+        if !app.is_loading() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    c.bench_function("frame 10k commits", |b| {
+        b.iter(|| ctx.run(raw_input(), |ctx| app.frame(ctx)));
+    });
+}
+
+criterion_group!(benches, frame);
+criterion_main!(benches);
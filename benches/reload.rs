@@ -0,0 +1,42 @@
+mod common;
+
+use clap::Parser;
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use kahva::Args;
+use kahva::backend::{self, ViewOptions};
+use kahva::jj::{LoadOptions, Repo};
+
+/// Builds the view of the whole log of synthetic repos, like a reload with "load all".
+fn reload(c: &mut Criterion) {
+    let mut group = c.benchmark_group("reload");
+    group.sample_size(10);
+    for commits in [10_000, 100_000] {
+        let dir = common::synthetic_repo(commits);
+        let repository = dir.to_str().unwrap();
+        let args = Args::parse_from(["kahva", "--repository", repository, "-r", "all()", "--ignore-working-copy"]);
+        let options = LoadOptions {
+            ignore_working_copy: true,
+            ..Default::default()
+        };
+        let repo = Repo::load_at(&dir, options).unwrap();
+
+        for collapse_stacks in [false, true] {
+            let options = ViewOptions {
+                limit: None,
+                collapse_stacks,
+                ..Default::default()
+            };
+            let name = match collapse_stacks {
+                true => "collapsed stacks",
+                false => "all commits",
+            };
+            group.bench_with_input(BenchmarkId::new(name, commits), &options, |b, options| {
+                b.iter(|| backend::reload(&repo, &args, options, || false).unwrap());
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, reload);
+criterion_main!(benches);
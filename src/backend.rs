@@ -4,125 +4,371 @@ use color_eyre::Result;
 use jj_cli::formatter::{FormatRecorder, Formatter, PlainTextFormatter};
 use jj_lib::backend::CommitId;
 use jj_lib::config::{ConfigGetError, ConfigGetResultExt};
-use jj_lib::graph::{GraphEdge, GraphEdgeType, TopoGroupedGraphIterator};
+use jj_lib::graph::{GraphEdge, GraphEdgeType, GraphNode, TopoGroupedGraphIterator};
+use jj_lib::object_id::ObjectId;
+use jj_lib::repo::ReadonlyRepo;
+use jj_lib::revset::RevsetEvaluationError;
 use jj_lib::settings::UserSettings;
 use renderdag::{Ancestor, GraphRow, GraphRowRenderer, Renderer};
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::io::Write;
+use std::sync::Arc;
+
+/// Number of commits loaded per page of the log.
+pub const PAGE_SIZE: usize = 200;
 
 pub struct CommitNode {
     pub commit_id: Option<CommitId>,
     pub msg: FormatRecorder,
+    /// Number of lines of `msg`, so the log can be laid out before it is formatted.
+    pub lines: usize,
     pub row: GraphRow<(CommitId, bool)>,
+    /// For synthetic "(elided revisions)" nodes, the edge whose commits are hidden.
+    pub elided: Option<ElidedEdge>,
+    /// Set for commits which are only shown because this elided edge was expanded.
+    pub revealed_by: Option<ElidedEdge>,
+    /// For rows summarising a collapsed linear stack, its commits from newest to oldest.
+    pub stack: Option<Vec<CommitId>>,
+    /// Set for the newest commit of an expanded stack, which can be collapsed again.
+    pub expanded_stack: bool,
+}
+
+/// An indirect edge in the graph, from a commit to one of its ancestors.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ElidedEdge {
+    pub descendant: CommitId,
+    pub ancestor: CommitId,
+}
+
+impl ElidedEdge {
+    /// All commits on the paths between the two endpoints, including the endpoints.
+    fn range_revset(&self) -> String {
+        format!("{}::{}", self.ancestor.hex(), self.descendant.hex())
+    }
+}
+
+#[derive(Clone, Default, PartialEq)]
+pub struct ViewOptions {
+    /// Additional revset the log is restricted to, e.g. `author(exact:"...")`.
+    pub filter: Option<String>,
+    /// Maximum number of commits to load, `None` loads the whole revset.
+    pub limit: Option<usize>,
+    /// Elided edges whose hidden commits are shown in place.
+    pub expanded_edges: Vec<ElidedEdge>,
+    /// Collapse runs of commits without forks, merges or references into a single row.
+    pub collapse_stacks: bool,
+    /// Newest commits of stacks which should not be collapsed.
+    pub expanded_stacks: HashSet<CommitId>,
 }
 
 #[derive(Default)]
 pub struct RepoView {
     pub nodes: Vec<CommitNode>,
-    #[expect(dead_code)]
+    /// The graph edge targets of each loaded commit.
     pub parents: HashMap<CommitId, Vec<CommitId>>,
-    pub heads: Vec<CommitId>,
+    /// Loaded commits without loaded children.
+    pub heads: HashSet<CommitId>,
+    /// Whether the revset contains more commits than were loaded.
+    pub has_more: bool,
+    /// When browsing a past operation, the loaded commits which were rewritten or abandoned since.
+    pub changed_since: HashSet<CommitId>,
+    /// Whether the working copy is stale and wasn't snapshotted.
+    pub working_copy_stale: bool,
 }
 
-pub fn reload(repo: &Repo, args: &Args) -> Result<RepoView> {
-    let log_revset = match &args.revisions {
-        Some(revset) => revset,
-        None => &repo
-            .settings()
-            .get_string("revsets.kahva-log")
-            .optional()
-            .transpose()
-            .unwrap_or_else(|| repo.settings().get_string("revsets.log"))?,
-    };
+/// Loads the first page of the log graph,
+/// or returns `None` once `is_stale` reports that the result isn't needed anymore.
+pub fn reload(
+    repo: &Repo,
+    args: &Args,
+    options: &ViewOptions,
+    is_stale: impl Fn() -> bool,
+) -> Result<Option<RepoView>> {
+    LogGraph::new(repo, args, options)?.next_page(repo, options.limit, is_stale)
+}
 
-    let prio_revset = repo
-        .settings()
-        .get_string("revsets.log-graph-prioritize")
-        .optional()?
-        .unwrap_or_else(|| "present(@)".to_owned());
-    let prio_revset = repo.revset_expression(&prio_revset)?;
-
-    // let log_template = repo.settings_commit_template("templates.log")?;
-    let log_template = repo.parse_commit_template("builtin_log_oneline")?;
-    let node_template = repo.parse_commit_opt_template(&get_node_template(repo.settings())?)?;
-    let use_elided_nodes = repo.settings().get_bool("ui.log-synthetic-elided-nodes")?;
-
-    let revset = repo.revset_expression(&log_revset)?.evaluate()?;
-    let has_commit = revset.containing_fn();
-    let mut iter = TopoGroupedGraphIterator::new(revset.iter_graph());
-
-    for prio in prio_revset.evaluate_to_commit_ids()? {
-        let prio = prio?;
-        if has_commit(&prio)? {
-            iter.prioritize_branch(prio);
-        }
+type GraphNodes<'repo> = Box<dyn Iterator<Item = Result<GraphNode<CommitId>, RevsetEvaluationError>> + 'repo>;
+type GraphWalk<'repo> = TopoGroupedGraphIterator<CommitId, GraphNodes<'repo>>;
+
+self_cell::self_cell!(
+    /// The walk through the log revset, which borrows the repo it was evaluated in.
+    struct RevsetWalk {
+        owner: Arc<ReadonlyRepo>,
+        #[not_covariant]
+        dependent: GraphWalk,
     }
+);
 
-    let mut nodes = Vec::new();
+/// The log graph loaded so far, kept between pages so that loading another one only walks and renders
+/// the new commits.
+pub struct LogGraph {
+    walk: RevsetWalk,
+    /// The options the graph is loaded with, its limit is the one of the first page.
+    options: ViewOptions,
+    renderer: GraphRowRenderer<(CommitId, bool)>,
+    /// The commits which are only shown because an elided edge was expanded.
+    revealed: HashMap<CommitId, ElidedEdge>,
+    /// The children of the walked commits, which come before their parents in the walk.
+    children: HashMap<CommitId, Vec<CommitId>>,
+    /// The next commit of the walk, taken from it to find out that there are more.
+    lookahead: Option<GraphNode<CommitId>>,
+    /// Number of commits walked so far.
+    loaded: usize,
+}
 
-    let mut graph = GraphRowRenderer::new();
+impl LogGraph {
+    pub fn new(repo: &Repo, args: &Args, options: &ViewOptions) -> Result<LogGraph> {
+        let log_revset = match &args.revisions {
+            Some(revset) => revset,
+            None => &repo
+                .settings()
+                .get_string("revsets.kahva-log")
+                .optional()
+                .transpose()
+                .unwrap_or_else(|| repo.settings().get_string("revsets.log"))?,
+        };
 
-    let mut parents: HashMap<CommitId, Vec<CommitId>> = HashMap::default();
+        let mut log_revset = match &options.filter {
+            Some(filter) => format!("({log_revset}) & ({filter})"),
+            None => log_revset.to_owned(),
+        };
+        let mut revealed = HashMap::new();
+        for edge in &options.expanded_edges {
+            log_revset = format!("({log_revset}) | {}", edge.range_revset());
 
-    for node in iter {
-        let (commit_id, edges) = node?;
-        parents
-            .entry(commit_id.clone())
-            .or_default()
-            .extend(edges.iter().map(|edge| edge.target.clone()));
+            let hidden = format!(
+                "{} ~ ({} | {})",
+                edge.range_revset(),
+                edge.ancestor.hex(),
+                edge.descendant.hex()
+            );
+            for commit_id in repo.revset_expression(&hidden)?.evaluate_to_commit_ids()? {
+                revealed.insert(commit_id?, edge.clone());
+            }
+        }
+
+        let prio_revset = repo
+            .settings()
+            .get_string("revsets.log-graph-prioritize")
+            .optional()?
+            .unwrap_or_else(|| "present(@)".to_owned());
+        let prio_commits = repo
+            .revset_expression(&prio_revset)?
+            .evaluate_to_commit_ids()?
+            .collect::<Result<Vec<_>, _>>()?;
 
-        let mut graphlog_edges = vec![];
-        let mut missing_edge_id = None;
-        let mut elided_targets = vec![];
-        for edge in edges {
-            match edge.edge_type {
-                GraphEdgeType::Missing => {
-                    missing_edge_id = Some(edge.target);
+        let expression = repo.revset_expression(&log_revset)?.resolve()?;
+        let walk = RevsetWalk::try_new(Arc::clone(repo.readonly_repo()), |readonly_repo| -> Result<_> {
+            let revset = expression.evaluate(readonly_repo.as_ref())?;
+            let has_commit = revset.containing_fn();
+            let mut walk = TopoGroupedGraphIterator::new(revset.iter_graph());
+            for prio in prio_commits {
+                if has_commit(&prio)? {
+                    walk.prioritize_branch(prio);
                 }
-                GraphEdgeType::Direct => {
-                    graphlog_edges.push(GraphEdge::direct((edge.target, false)));
+            }
+            Ok(walk)
+        })?;
+
+        Ok(LogGraph {
+            walk,
+            options: options.clone(),
+            renderer: GraphRowRenderer::new(),
+            revealed,
+            children: HashMap::new(),
+            lookahead: None,
+            loaded: 0,
+        })
+    }
+
+    /// Whether the view for `options` is this graph with more commits, so it can be loaded
+    /// with [`LogGraph::next_page`] instead of from the start.
+    pub fn continues_with(&self, repo: &Repo, options: &ViewOptions) -> bool {
+        let same_options = ViewOptions {
+            limit: self.options.limit,
+            ..options.clone()
+        } == self.options;
+        Arc::ptr_eq(self.walk.borrow_owner(), repo.readonly_repo())
+            && same_options
+            && options.limit.is_none_or(|limit| limit >= self.loaded)
+    }
+
+    /// Walks and renders the commits after the loaded ones, up to `limit` commits in total.
+    ///
+    /// Returns `None` once `is_stale` reports that the result isn't needed anymore,
+    /// the graph can't be continued then.
+    pub fn next_page(
+        &mut self,
+        repo: &Repo,
+        limit: Option<usize>,
+        is_stale: impl Fn() -> bool,
+    ) -> Result<Option<RepoView>> {
+        let mut graph_nodes = Vec::new();
+        let has_more = loop {
+            if is_stale() {
+                return Ok(None);
+            }
+            let node = match self.lookahead.take() {
+                Some(node) => node,
+                None => match self.walk.with_dependent_mut(|_, walk| walk.next()) {
+                    Some(node) => node?,
+                    None => break false,
+                },
+            };
+            if limit.is_some_and(|limit| self.loaded >= limit) {
+                self.lookahead = Some(node);
+                break true;
+            }
+            self.loaded += 1;
+            let (commit_id, edges) = &node;
+            for edge in edges {
+                self.children
+                    .entry(edge.target.clone())
+                    .or_default()
+                    .push(commit_id.clone());
+            }
+            graph_nodes.push(node);
+        };
+
+        let parents: HashMap<CommitId, Vec<CommitId>> = graph_nodes
+            .iter()
+            .map(|(commit_id, edges)| {
+                let targets = edges.iter().map(|edge| edge.target.clone()).collect();
+                (commit_id.clone(), targets)
+            })
+            .collect();
+        // children are walked before their parents, so commits without loaded children stay heads
+        let heads = parents
+            .keys()
+            .filter(|&commit| !self.children.contains_key(commit))
+            .cloned()
+            .collect();
+
+        let Some(nodes) = self.render(repo, graph_nodes, is_stale)? else {
+            return Ok(None);
+        };
+
+        let changed_since = match repo.at_operation() {
+            Some(_) => repo.hidden_at_head(parents.keys().cloned().collect())?,
+            None => HashSet::new(),
+        };
+
+        Ok(Some(RepoView {
+            nodes,
+            parents,
+            heads,
+            has_more,
+            changed_since,
+            working_copy_stale: repo.is_working_copy_stale(),
+        }))
+    }
+
+    /// Renders the rows of newly walked commits.
+    ///
+    /// Stacks are only found within them, one reaching into the next page is continued by another row.
+    fn render(
+        &mut self,
+        repo: &Repo,
+        graph_nodes: Vec<GraphNode<CommitId>>,
+        is_stale: impl Fn() -> bool,
+    ) -> Result<Option<Vec<CommitNode>>> {
+        let log_template = repo
+            .settings()
+            .get_string("templates.kahva-log")
+            .optional()
+            .transpose()
+            .unwrap_or_else(|| repo.settings().get_string("templates.log"))?;
+        let log_template = repo.parse_commit_template(&log_template)?;
+        let node_template = repo.parse_commit_opt_template(&get_node_template(repo.settings())?)?;
+        let use_elided_nodes = repo.settings().get_bool("ui.log-synthetic-elided-nodes")?;
+
+        let stacks = match self.options.collapse_stacks {
+            true => find_linear_stacks(repo, &graph_nodes, &self.children, |commit_id| {
+                self.revealed.contains_key(commit_id)
+            }),
+            false => HashMap::new(),
+        };
+        let stack_template = repo.parse_commit_template("change_id.shortest(8)")?;
+
+        let mut nodes = Vec::new();
+
+        let graph = &mut self.renderer;
+
+        let mut graph_nodes = graph_nodes.into_iter().enumerate();
+        while let Some((i, (commit_id, edges))) = graph_nodes.next() {
+            if is_stale() {
+                return Ok(None);
+            }
+            // expanded stacks are shown commit by commit, but are still found so they can be collapsed again
+            let stack_len = stacks.get(&i).copied();
+            let expanded_stack = stack_len.is_some() && self.options.expanded_stacks.contains(&commit_id);
+            if let Some(len) = stack_len.filter(|_| !expanded_stack) {
+                let mut stack = vec![commit_id];
+                let mut edges = edges;
+                for (_, (commit_id, next_edges)) in graph_nodes.by_ref().take(len - 1) {
+                    stack.push(commit_id);
+                    edges = next_edges;
                 }
-                GraphEdgeType::Indirect => {
-                    if use_elided_nodes {
-                        elided_targets.push(edge.target.clone());
-                        graphlog_edges.push(GraphEdge::direct((edge.target, true)));
-                    } else {
-                        graphlog_edges.push(GraphEdge::indirect((edge.target, false)));
+
+                let key = (stack[0].clone(), false);
+                let edges = edges
+                    .into_iter()
+                    .map(|edge| edge.map(|target| (target, false)))
+                    .collect::<Vec<_>>();
+                let row = graph.next_row(
+                    key,
+                    edges.iter().map(convert_graph_edge_into_ancestor).collect(),
+                    "o".to_owned(),
+                    String::new(),
+                );
+
+                let mut f = FormatRecorder::new();
+                f.push_label("collapsed")?;
+                write!(f, "{} commits ", stack.len())?;
+                stack_template.format(&repo.commit(stack.last().unwrap())?, &mut f)?;
+                f.write_all(b"..")?;
+                stack_template.format(&repo.commit(&stack[0])?, &mut f)?;
+                f.pop_label()?;
+                nodes.push(CommitNode {
+                    commit_id: None,
+                    lines: line_count(&f),
+                    msg: f,
+                    row,
+                    elided: None,
+                    revealed_by: None,
+                    stack: Some(stack),
+                    expanded_stack: false,
+                });
+                continue;
+            }
+
+            let mut graphlog_edges = vec![];
+            let mut missing_edge_id = None;
+            let mut elided_targets = vec![];
+            for edge in edges {
+                match edge.edge_type {
+                    GraphEdgeType::Missing => {
+                        missing_edge_id = Some(edge.target);
+                    }
+                    GraphEdgeType::Direct => {
+                        graphlog_edges.push(GraphEdge::direct((edge.target, false)));
+                    }
+                    GraphEdgeType::Indirect => {
+                        if use_elided_nodes {
+                            elided_targets.push(edge.target.clone());
+                            graphlog_edges.push(GraphEdge::direct((edge.target, true)));
+                        } else {
+                            graphlog_edges.push(GraphEdge::indirect((edge.target, false)));
+                        }
                     }
                 }
             }
-        }
-        if let Some(missing_edge_id) = missing_edge_id {
-            graphlog_edges.push(GraphEdge::missing((missing_edge_id, false)));
-        }
-        let key = (commit_id.clone(), false);
-        let commit = repo.commit(&key.0)?;
-
-        let mut node_out = Vec::new();
-        let mut f = PlainTextFormatter::new(&mut node_out);
-        node_template.format(&Some(commit.clone()), &mut f)?;
-        let _node_symbol = String::from_utf8(node_out)?;
-        let node_symbol = "o";
-
-        let row = graph.next_row(
-            key,
-            graphlog_edges.iter().map(convert_graph_edge_into_ancestor).collect(),
-            node_symbol.into(),
-            String::new(),
-        );
-        let mut f = FormatRecorder::new();
-        log_template.format(&commit, &mut f)?;
-        nodes.push(CommitNode {
-            commit_id: Some(commit_id.clone()),
-            msg: f,
-            row,
-        });
-
-        for elided_target in elided_targets {
-            let elided_key = (elided_target.clone(), true);
-            let real_key = (elided_key.0.clone(), false);
-            let edges = [GraphEdge::direct(real_key)];
+            if let Some(missing_edge_id) = missing_edge_id {
+                graphlog_edges.push(GraphEdge::missing((missing_edge_id, false)));
+            }
+            let key = (commit_id.clone(), false);
+            let commit = repo.commit(&key.0)?;
 
             let mut node_out = Vec::new();
             let mut f = PlainTextFormatter::new(&mut node_out);
@@ -130,32 +376,106 @@ pub fn reload(repo: &Repo, args: &Args) -> Result<RepoView> {
             let _node_symbol = String::from_utf8(node_out)?;
             let node_symbol = "o";
 
-            let edges = edges.iter().map(convert_graph_edge_into_ancestor).collect();
             let row = graph.next_row(
-                elided_key,
-                edges,
-                node_symbol.to_owned(),
-                "(elided revisions)".to_owned(),
+                key,
+                graphlog_edges.iter().map(convert_graph_edge_into_ancestor).collect(),
+                node_symbol.into(),
+                String::new(),
             );
             let mut f = FormatRecorder::new();
-            f.push_label("elided")?;
-            f.write_all(b"(elided revisions)")?;
-            f.pop_label()?;
+            log_template.format(&commit, &mut f)?;
             nodes.push(CommitNode {
-                commit_id: None,
+                commit_id: Some(commit_id.clone()),
+                lines: line_count(&f),
                 msg: f,
                 row,
+                elided: None,
+                revealed_by: self.revealed.get(&commit_id).cloned(),
+                stack: None,
+                expanded_stack,
             });
+
+            for elided_target in elided_targets {
+                let elided_key = (elided_target.clone(), true);
+                let real_key = (elided_key.0.clone(), false);
+                let edges = [GraphEdge::direct(real_key)];
+
+                let mut node_out = Vec::new();
+                let mut f = PlainTextFormatter::new(&mut node_out);
+                node_template.format(&Some(commit.clone()), &mut f)?;
+                let _node_symbol = String::from_utf8(node_out)?;
+                let node_symbol = "o";
+
+                let edges = edges.iter().map(convert_graph_edge_into_ancestor).collect();
+                let row = graph.next_row(
+                    elided_key,
+                    edges,
+                    node_symbol.to_owned(),
+                    "(elided revisions)".to_owned(),
+                );
+                let mut f = FormatRecorder::new();
+                f.push_label("elided")?;
+                f.write_all(b"(elided revisions)")?;
+                f.pop_label()?;
+                nodes.push(CommitNode {
+                    commit_id: None,
+                    lines: line_count(&f),
+                    msg: f,
+                    row,
+                    elided: Some(ElidedEdge {
+                        descendant: commit_id.clone(),
+                        ancestor: elided_target,
+                    }),
+                    revealed_by: None,
+                    stack: None,
+                    expanded_stack: false,
+                });
+            }
         }
+
+        Ok(Some(nodes))
     }
+}
 
-    let heads = parents
-        .keys()
-        .filter(|&commit| !parents.values().flatten().any(|x| x == commit))
-        .cloned()
-        .collect();
+/// Minimum number of commits in a linear run before it is collapsed into a single row.
+const MIN_STACK_LEN: usize = 3;
 
-    Ok(RepoView { nodes, parents, heads })
+/// Finds runs of consecutive nodes where each commit is the only parent of the previous one,
+/// and none of them is a fork, a merge or referenced by a bookmark, tag or working copy.
+///
+/// Returns the length of each run keyed by the index of its first node.
+fn find_linear_stacks(
+    repo: &Repo,
+    graph_nodes: &[GraphNode<CommitId>],
+    children: &HashMap<CommitId, Vec<CommitId>>,
+    skip: impl Fn(&CommitId) -> bool,
+) -> HashMap<usize, usize> {
+    let referenced: HashSet<&CommitId> = repo.inner().view().all_referenced_commit_ids().collect();
+
+    let is_linear = |(commit_id, edges): &GraphNode<CommitId>| {
+        matches!(edges.as_slice(), [edge] if edge.edge_type == GraphEdgeType::Direct)
+            && children.get(commit_id).is_none_or(|children| children.len() <= 1)
+            && !referenced.contains(commit_id)
+            && !skip(commit_id)
+    };
+
+    let mut stacks = HashMap::new();
+    let mut start = 0;
+    while start < graph_nodes.len() {
+        let mut end = start;
+        while end < graph_nodes.len() && is_linear(&graph_nodes[end]) {
+            end += 1;
+            let parent = &graph_nodes[end - 1].1[0].target;
+            if graph_nodes.get(end).is_none_or(|(next, _)| next != parent) {
+                break;
+            }
+        }
+        if end - start >= MIN_STACK_LEN {
+            stacks.insert(start, end - start);
+        }
+        start = end.max(start + 1);
+    }
+    stacks
 }
 
 fn convert_graph_edge_into_ancestor<K: Clone>(e: &GraphEdge<K>) -> Ancestor<K> {
@@ -170,3 +490,12 @@ fn get_node_template(settings: &UserSettings) -> Result<Cow<'static, str>, Confi
     let symbol = settings.get_string("templates.log_node").optional()?;
     Ok(symbol.map(Cow::Owned).unwrap_or(Cow::Borrowed("builtin_log_node")))
 }
+
+/// The number of lines the formatted output has, ignoring trailing empty lines like the formatter does.
+fn line_count(f: &FormatRecorder) -> usize {
+    let mut lines: Vec<&[u8]> = f.data().split(|b| *b == b'\n').collect();
+    while lines.len() > 1 && lines.last().unwrap().is_empty() {
+        lines.pop();
+    }
+    lines.len()
+}
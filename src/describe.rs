@@ -0,0 +1,128 @@
+use color_eyre::Result;
+use jj_lib::config::{ConfigGetResultExt, StackedConfig};
+
+/// Hints about descriptions shown while editing them, configured in `[kahva.describe]`.
+#[derive(Debug, Default)]
+pub struct DescribeLints {
+    /// Subjects longer than this many characters get a hint.
+    subject_length: Option<usize>,
+    /// Trailers every description should end with, e.g. `Signed-off-by`.
+    required_trailers: Vec<String>,
+}
+
+impl DescribeLints {
+    pub fn from_config(config: &StackedConfig) -> Result<DescribeLints> {
+        let subject_length = config.get::<usize>("kahva.describe.subject-length").optional()?;
+        let required_trailers = config
+            .get::<Vec<String>>("kahva.describe.required-trailers")
+            .optional()?
+            .unwrap_or_default();
+        Ok(DescribeLints {
+            subject_length,
+            required_trailers,
+        })
+    }
+
+    /// The hints for a description, which is expected to be cleaned up already.
+    pub fn lint(&self, description: &str) -> Vec<String> {
+        let mut hints = Vec::new();
+        if description.trim().is_empty() {
+            return hints;
+        }
+
+        let subject = description.lines().next().unwrap_or_default();
+        let subject_length = subject.chars().count();
+        if let Some(limit) = self.subject_length.filter(|&limit| subject_length > limit) {
+            hints.push(format!("The subject is {subject_length} characters long, more than {limit}"));
+        }
+
+        let trailers = trailers(description);
+        for required in &self.required_trailers {
+            if !trailers.iter().any(|(key, _)| key.eq_ignore_ascii_case(required)) {
+                hints.push(format!("The `{required}` trailer is missing"));
+            }
+        }
+        hints
+    }
+}
+
+/// Removes the `JJ:` comment lines and everything after `JJ: ignore-rest`,
+/// like jj does after a description was edited.
+pub fn cleanup_description(text: &str) -> String {
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        if line.starts_with("JJ: ignore-rest") {
+            break;
+        }
+        if !line.starts_with("JJ:") {
+            lines.push(line.trim_end());
+        }
+    }
+    let description = lines.join("\n");
+    let description = description.trim_matches('\n');
+    match description.is_empty() {
+        true => String::new(),
+        false => format!("{description}\n"),
+    }
+}
+
+/// The `Key: value` lines of the last paragraph, if all of its lines are trailers
+/// and it isn't the subject.
+fn trailers(description: &str) -> Vec<(&str, &str)> {
+    let paragraphs: Vec<&str> = description.trim_end().split("\n\n").collect();
+    let [_, .., last] = paragraphs.as_slice() else {
+        return Vec::new();
+    };
+    let trailers: Option<Vec<_>> = last.lines().map(parse_trailer).collect();
+    trailers.unwrap_or_default()
+}
+
+fn parse_trailer(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.split_once(": ")?;
+    let is_key = !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    is_key.then_some((key, value.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lints(subject_length: Option<usize>, required_trailers: &[&str]) -> DescribeLints {
+        DescribeLints {
+            subject_length,
+            required_trailers: required_trailers.iter().map(|trailer| trailer.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn cleanup_ignore_rest() {
+        let text = "subject\nJJ: comment\n\nbody  \nJJ: ignore-rest\ndiff --git a/file b/file\n";
+        assert_eq!(cleanup_description(text), "subject\n\nbody\n");
+        assert_eq!(cleanup_description("JJ: ignore-rest\nsubject\n"), "");
+    }
+
+    #[test]
+    fn subject_only_has_no_trailers() {
+        assert_eq!(trailers("Signed-off-by: Someone\n"), Vec::new());
+        let hints = lints(None, &["Signed-off-by"]).lint("Signed-off-by: Someone\n");
+        assert_eq!(hints, ["The `Signed-off-by` trailer is missing"]);
+    }
+
+    #[test]
+    fn trailers_of_last_paragraph() {
+        let description = "subject\n\nbody\n\nSigned-off-by: Someone\nChange-Id: I1234\n";
+        assert_eq!(trailers(description), [("Signed-off-by", "Someone"), ("Change-Id", "I1234")]);
+        assert!(lints(None, &["change-id"]).lint(description).is_empty());
+
+        // prose in the last paragraph means it isn't a trailer block
+        let description = "subject\n\nSee: the issue\nwhich explains it\n";
+        assert_eq!(trailers(description), Vec::new());
+    }
+
+    #[test]
+    fn subject_length_counts_chars() {
+        let lints = lints(Some(5), &[]);
+        assert!(lints.lint("äöüßé\n\nbody which is longer\n").is_empty());
+        assert_eq!(lints.lint("äöüßéa\n"), ["The subject is 6 characters long, more than 5"]);
+    }
+}
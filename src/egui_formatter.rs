@@ -1,13 +1,53 @@
+use crate::links::AutoLinks;
+use crate::theme::ColorTheme;
 use egui::TextFormat;
 use egui::text::LayoutJob;
 use jj_cli::formatter::{Color, Formatter, Style};
-use jj_lib::config::{ConfigGetError, StackedConfig};
+use jj_lib::config::{ConfigGetError, ConfigGetResultExt, StackedConfig};
 use std::collections::HashMap;
 use std::io::{self, Error, Write};
 use std::sync::Arc;
 
 type Rules = Vec<(Vec<String>, Style)>;
 
+/// A span of formatted text.
+#[derive(Clone, Debug, Default)]
+pub struct Section {
+    pub job: LayoutJob,
+    /// The notable label path this span was written with, space-separated.
+    pub label: Option<String>,
+    /// The hyperlink target of this span, if any.
+    pub link: Option<String>,
+    /// The label stack of each of the `job`'s sections, only recorded in debug mode.
+    pub label_stacks: Vec<Vec<String>>,
+}
+
+impl Section {
+    fn new(label: Option<String>, link: Option<String>) -> Section {
+        Section {
+            label,
+            link,
+            ..Default::default()
+        }
+    }
+}
+
+/// The size of the formatted text. Every line is exactly `line_height` high, so it lines up with the graph.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextSize {
+    pub font_size: f32,
+    pub line_height: f32,
+}
+
+impl Default for TextSize {
+    fn default() -> Self {
+        TextSize {
+            font_size: 14.0,
+            line_height: 20.0,
+        }
+    }
+}
+
 fn default_format() -> TextFormat {
     TextFormat {
         line_height: Some(20.0),
@@ -17,11 +57,20 @@ fn default_format() -> TextFormat {
 
 #[derive(Debug)]
 pub struct ColorFormatter {
-    egui_output: Vec<(LayoutJob, Option<String>)>,
+    /// The sections of each output line.
+    egui_output: Vec<Vec<Section>>,
     egui_format: TextFormat,
     output: Vec<u8>,
 
     rules: Arc<Rules>,
+    /// Label paths which are put into their own [`Section`], e.g. `["bookmarks", "name"]`.
+    notable_labels: Vec<Vec<String>>,
+    /// Patterns which are linked in text outside of notable labels.
+    links: Arc<AutoLinks>,
+    /// The target of the hyperlink currently being written.
+    link: Option<String>,
+    theme: ColorTheme,
+    text_size: TextSize,
     /// The stack of currently applied labels. These determine the desired
     /// style.
     labels: Vec<String>,
@@ -31,81 +80,137 @@ pub struct ColorFormatter {
     /// The debug string (space-separated labels) we last wrote to the output.
     /// Initialize to None to turn debug strings off.
     current_debug: Option<String>,
+    /// The labels the pending output was written with, only tracked in debug mode.
+    output_labels: Vec<String>,
 }
 
 impl ColorFormatter {
-    pub fn new(rules: Arc<Rules>, debug: bool) -> ColorFormatter {
+    pub fn new(
+        rules: Arc<Rules>,
+        notable_labels: Vec<Vec<String>>,
+        links: Arc<AutoLinks>,
+        debug: bool,
+    ) -> ColorFormatter {
+        let theme = ColorTheme::dark();
+        let text_size = TextSize::default();
         ColorFormatter {
-            egui_output: vec![(LayoutJob::default(), None)],
-            egui_format: default_format(),
+            egui_output: vec![vec![Section::new(None, None)]],
+            egui_format: text_format(&Style::default(), &theme, text_size),
             output: Vec::new(),
             rules,
+            notable_labels,
+            links,
+            link: None,
+            theme,
+            text_size,
             labels: vec![],
             cached_styles: HashMap::new(),
             current_style: Style::default(),
             current_debug: debug.then(String::new),
+            output_labels: Vec::new(),
         }
     }
 
-    pub fn for_config(config: &StackedConfig, debug: bool) -> Result<Self, ConfigGetError> {
+    pub fn for_config(
+        config: &StackedConfig,
+        notable_labels: Vec<Vec<String>>,
+        links: AutoLinks,
+        debug: bool,
+    ) -> Result<Self, ConfigGetError> {
         let mut rules = jj_cli::formatter::rules_from_config(config)?;
         rules.push((vec!["change_id".to_owned()], Style {
             fg: Some(Color::Magenta),
             monospace: Some(true),
             ..Default::default()
         }));
-        Ok(Self::new(Arc::new(rules), debug))
+        Ok(Self::new(Arc::new(rules), notable_labels, Arc::new(links), debug))
+    }
+
+    pub fn theme(&self) -> &ColorTheme {
+        &self.theme
+    }
+
+    pub fn set_theme(&mut self, theme: ColorTheme) {
+        self.theme = theme;
+        self.egui_format = text_format(&self.current_style, &self.theme, self.text_size);
     }
 
-    pub fn take(&mut self) -> Vec<(LayoutJob, Option<String>)> {
+    pub fn is_debug(&self) -> bool {
+        self.current_debug.is_some()
+    }
+
+    pub fn set_debug(&mut self, debug: bool) {
+        self.current_debug = debug.then(String::new);
+    }
+
+    pub fn text_size(&self) -> TextSize {
+        self.text_size
+    }
+
+    pub fn set_text_size(&mut self, text_size: TextSize) {
+        self.text_size = text_size;
+        self.egui_format = text_format(&self.current_style, &self.theme, self.text_size);
+    }
+
+    /// Takes the output written so far, split into lines.
+    pub fn take(&mut self) -> Vec<Vec<Section>> {
+        // a failed replay can leave a debug marker open, which would otherwise be closed in the next row
+        if self.current_debug.as_ref().is_some_and(|current| !current.is_empty()) {
+            self.output.extend_from_slice(b">>");
+            self.current_debug = Some(String::new());
+        }
         self.flush_to_egui();
-        self.egui_format = default_format();
+        self.egui_format = text_format(&Style::default(), &self.theme, self.text_size);
         self.current_style = Style::default();
+        self.link = None;
+        // left over if replaying failed halfway
+        self.labels.clear();
 
-        let mut output = std::mem::replace(&mut self.egui_output, vec![(LayoutJob::default(), None)]);
+        let mut output = std::mem::replace(&mut self.egui_output, vec![vec![Section::new(None, None)]]);
 
-        if let Some((last, _)) = output.last_mut() {
-            if let Some(last_section) = last.sections.last_mut() {
-                if last.text.ends_with('\n') && last_section.byte_range.len() == 1 {
-                    last.sections.pop();
-                }
-            }
+        // templates usually end with a newline, which would leave an empty line at the end
+        while output.len() > 1 && output.last().unwrap().iter().all(|section| section.job.is_empty()) {
+            output.pop();
         }
 
         output
     }
 
-    fn requested_style(&mut self) -> Style {
-        if let Some(cached) = self.cached_styles.get(&self.labels) {
-            cached.clone()
-        } else {
-            // We use the reverse list of matched indices as a measure of how well the rule
-            // matches the actual labels. For example, for rule "a d" and the actual labels
-            // "a b c d", we'll get [3,0]. We compare them by Rust's default Vec comparison.
-            // That means "a d" will trump both rule "d" (priority [3]) and rule
-            // "a b c" (priority [2,1,0]).
-            let mut matched_styles = vec![];
-            for (labels, style) in self.rules.as_ref() {
-                let mut labels_iter = self.labels.iter().enumerate();
-                // The indexes in the current label stack that match the required label.
-                let mut matched_indices = vec![];
-                for required_label in labels {
-                    for (label_index, label) in &mut labels_iter {
-                        if label == required_label {
-                            matched_indices.push(label_index);
-                            break;
-                        }
+    /// The rules matching the label stack with their priorities, from lowest to highest priority.
+    pub fn matched_rules(&self, labels: &[String]) -> Vec<(&[String], &Style, Vec<usize>)> {
+        // We use the reverse list of matched indices as a measure of how well the rule
+        // matches the actual labels. For example, for rule "a d" and the actual labels
+        // "a b c d", we'll get [3,0]. We compare them by Rust's default Vec comparison.
+        // That means "a d" will trump both rule "d" (priority [3]) and rule
+        // "a b c" (priority [2,1,0]).
+        let mut matched_styles = vec![];
+        for (rule_labels, style) in self.rules.as_ref() {
+            let mut labels_iter = labels.iter().enumerate();
+            // The indexes in the current label stack that match the required label.
+            let mut matched_indices = vec![];
+            for required_label in rule_labels {
+                for (label_index, label) in &mut labels_iter {
+                    if label == required_label {
+                        matched_indices.push(label_index);
+                        break;
                     }
                 }
-                if matched_indices.len() == labels.len() {
-                    matched_indices.reverse();
-                    matched_styles.push((style, matched_indices));
-                }
             }
-            matched_styles.sort_by_key(|(_, indices)| indices.clone());
+            if matched_indices.len() == rule_labels.len() {
+                matched_indices.reverse();
+                matched_styles.push((rule_labels.as_slice(), style, matched_indices));
+            }
+        }
+        matched_styles.sort_by(|(_, _, a), (_, _, b)| a.cmp(b));
+        matched_styles
+    }
 
+    fn requested_style(&mut self) -> Style {
+        if let Some(cached) = self.cached_styles.get(&self.labels) {
+            cached.clone()
+        } else {
             let mut style = Style::default();
-            for (matched_style, _) in matched_styles {
+            for (_, matched_style, _) in self.matched_rules(&self.labels) {
                 style.merge(matched_style);
             }
             self.cached_styles.insert(self.labels.clone(), style.clone());
@@ -130,60 +235,12 @@ impl ColorFormatter {
             }
             None => None,
         };
+        if self.current_debug.is_some() {
+            self.output_labels.clone_from(&self.labels);
+        }
         let new_style = self.requested_style();
         if new_style != self.current_style {
-            if new_style.bold != self.current_style.bold {
-                if new_style.bold.unwrap_or_default() {
-                    // TODO queue!(self.output, SetAttribute(Attribute::Bold))?;
-                } else {
-                    // NoBold results in double underlining on some terminals, so we use reset
-                    // instead. However, that resets other attributes as well, so we reset
-                    // our record of the current style so we re-apply the other attributes
-                    // below.
-                    // queue!(self.output, SetAttribute(Attribute::Reset))?;
-                    self.current_style = Style::default();
-                    self.egui_format = default_format();
-                }
-            }
-            if new_style.italic != self.current_style.italic {
-                if new_style.italic.unwrap_or_default() {
-                    // queue!(self.output, SetAttribute(Attribute::Italic))?;
-                    self.egui_format.italics = true;
-                } else {
-                    // queue!(self.output, SetAttribute(Attribute::NoItalic))?;
-                    self.egui_format.italics = false;
-                }
-            }
-            if new_style.underline != self.current_style.underline {
-                if new_style.underline.unwrap_or_default() {
-                    // queue!(self.output, SetAttribute(Attribute::Underlined))?;
-                    self.egui_format.underline = egui::Stroke::new(2.0, default_color());
-                } else {
-                    // queue!(self.output, SetAttribute(Attribute::NoUnderline))?;
-                    self.egui_format.underline = egui::Stroke::NONE;
-                }
-            }
-            if new_style.fg != self.current_style.fg {
-                /*queue!(
-                    self.output,
-                    SetForegroundColor(new_style.fg.unwrap_or(Color::Reset))
-                )?;*/
-                self.egui_format.color = new_style.fg.map(color_to_egui).unwrap_or_else(default_color);
-            }
-            if new_style.bg != self.current_style.bg {
-                /*queue!(
-                    self.output,
-                    SetBackgroundColor(new_style.bg.unwrap_or(Color::Reset))
-                )?;*/
-                self.egui_format.color = new_style.bg.map(color_to_egui).unwrap_or_else(default_color);
-            }
-            if new_style.monospace != self.current_style.monospace {
-                let family = match new_style.monospace.unwrap_or(false) {
-                    true => egui::FontFamily::Monospace,
-                    false => egui::FontFamily::Proportional,
-                };
-                self.egui_format.font_id = egui::FontId::new(14.0, family);
-            }
+            self.egui_format = text_format(&new_style, &self.theme, self.text_size);
             self.current_style = new_style;
         }
         if let Some(d) = new_debug {
@@ -203,39 +260,151 @@ impl ColorFormatter {
 
         // self.egui_output.push(LayoutJob::default());
 
-        self.egui_output
-            .last_mut()
-            .unwrap()
-            .0
-            .append(&out, 0.0, self.egui_format.clone());
+        let format = self.egui_format.clone();
+        let debug_labels = self.current_debug.is_some().then(|| self.output_labels.clone());
+        let section = self.current_section();
+        section.job.append(&out, 0.0, format);
+        if let Some(labels) = debug_labels {
+            section.label_stacks.push(labels);
+        }
         self.output.clear();
     }
+
+    fn current_section(&mut self) -> &mut Section {
+        self.egui_output.last_mut().unwrap().last_mut().unwrap()
+    }
+
+    fn push_section(&mut self, label: Option<String>) {
+        self.flush_to_egui();
+        let link = self.link.clone();
+        self.egui_output.last_mut().unwrap().push(Section::new(label, link));
+    }
+
+    /// Starts a new output line, continuing the link of the current section.
+    ///
+    /// The style continues as well, but not the notable label: only the first line of a
+    /// multi-line span is interactive, e.g. the subject of a description.
+    fn new_line(&mut self) {
+        self.flush_to_egui();
+        let link = self.link.clone();
+        self.egui_output.push(vec![Section::new(None, link)]);
+    }
+
+    fn set_link(&mut self, link: Option<String>) {
+        if self.link != link {
+            self.link = link;
+            let label = self.current_section().label.clone();
+            self.push_section(label);
+        }
+    }
+
+    /// Writes text, linking matches of the [`AutoLinks`] unless it already is a hyperlink
+    /// or part of a notable label.
+    fn write_text(&mut self, text: &[u8]) -> io::Result<()> {
+        if self.links.is_empty() || self.link.is_some() || self.current_section().label.is_some() {
+            return write_sanitized(&mut self.output, text);
+        }
+
+        let text = String::from_utf8_lossy(text);
+        let mut last = 0;
+        for (range, url) in self.links.find(&text) {
+            write_sanitized(&mut self.output, text[last..range.start].as_bytes())?;
+            self.set_link(Some(url));
+            write_sanitized(&mut self.output, text[range.clone()].as_bytes())?;
+            self.set_link(None);
+            last = range.end;
+        }
+        write_sanitized(&mut self.output, text[last..].as_bytes())
+    }
 }
 
-fn default_color() -> egui::Color32 {
-    egui::Color32::WHITE
+/// A short description of the attributes set in a style, e.g. `fg=Red bold`.
+pub fn style_summary(style: &Style) -> String {
+    let mut parts = Vec::new();
+    if let Some(fg) = style.fg {
+        parts.push(format!("fg={fg:?}"));
+    }
+    if let Some(bg) = style.bg {
+        parts.push(format!("bg={bg:?}"));
+    }
+    let flags = [
+        ("bold", style.bold),
+        ("italic", style.italic),
+        ("underline", style.underline),
+        ("reverse", style.reverse),
+        ("monospace", style.monospace),
+    ];
+    for (name, value) in flags {
+        match value {
+            Some(true) => parts.push(name.to_owned()),
+            Some(false) => parts.push(format!("no-{name}")),
+            None => {}
+        }
+    }
+    parts.join(" ")
 }
-fn color_to_egui(color: Color) -> egui::Color32 {
-    match color {
-        Color::Black => egui::Color32::from_rgb(0, 0, 0),
-        Color::Red => egui::Color32::from_rgb(187, 0, 0),
-        Color::Green => egui::Color32::from_rgb(0, 187, 0),
-        Color::Yellow => egui::Color32::from_rgb(187, 187, 0),
-        Color::Blue => egui::Color32::from_rgb(0, 0, 187),
-        Color::Magenta => egui::Color32::from_rgb(187, 0, 187),
-        Color::Cyan => egui::Color32::from_rgb(0, 187, 187),
-        Color::Grey => egui::Color32::from_rgb(85, 85, 85),
-        Color::DarkGrey => egui::Color32::from_rgb(85, 85, 85),
-        Color::DarkRed => egui::Color32::from_rgb(255, 85, 85),
-        Color::DarkGreen => egui::Color32::from_rgb(85, 255, 85),
-        Color::DarkBlue => egui::Color32::from_rgb(85, 85, 255),
-        Color::DarkMagenta => egui::Color32::from_rgb(255, 85, 255),
-        Color::DarkCyan => egui::Color32::from_rgb(85, 255, 255),
-        Color::DarkYellow => egui::Color32::from_rgb(187, 187, 0),
-        Color::White => egui::Color32::from_rgb(255, 255, 255),
-        Color::Reset => default_color(),
-        Color::Rgb { r, g, b } => egui::Color32::from_rgb(r, g, b),
-        Color::AnsiValue(_) => todo!(),
+
+/// Font family for bold proportional text, see [`add_bold_fonts`].
+pub const BOLD_FAMILY: &str = "bold";
+/// Font family for bold monospace text, see [`add_bold_fonts`].
+pub const BOLD_MONOSPACE_FAMILY: &str = "bold-monospace";
+
+/// Registers the font families used for bold text.
+///
+/// egui doesn't ship bold fonts, so unless `kahva.fonts.bold` and `kahva.fonts.bold-monospace`
+/// point to font files, these fall back to the regular fonts and bold text looks like regular text.
+pub fn add_bold_fonts(fonts: &mut egui::FontDefinitions, config: &StackedConfig) -> color_eyre::Result<()> {
+    let families = [
+        (BOLD_FAMILY, "kahva.fonts.bold", egui::FontFamily::Proportional),
+        (BOLD_MONOSPACE_FAMILY, "kahva.fonts.bold-monospace", egui::FontFamily::Monospace),
+    ];
+    for (family, config_key, fallback) in families {
+        let mut font_names = Vec::new();
+        if let Some(path) = config.get::<String>(config_key).optional()? {
+            let data = std::fs::read(&path)
+                .map_err(|e| color_eyre::eyre::eyre!("Failed to read font {path} from {config_key}: {e}"))?;
+            fonts
+                .font_data
+                .insert(family.to_owned(), Arc::new(egui::FontData::from_owned(data)));
+            font_names.push(family.to_owned());
+        }
+        font_names.extend(fonts.families.get(&fallback).cloned().unwrap_or_default());
+        fonts
+            .families
+            .insert(egui::FontFamily::Name(family.into()), font_names);
+    }
+    Ok(())
+}
+
+fn text_format(style: &Style, theme: &ColorTheme, text_size: TextSize) -> TextFormat {
+    let mut fg = theme.color(style.fg.unwrap_or(Color::Reset));
+    let mut bg = style.bg.map_or(egui::Color32::TRANSPARENT, |bg| theme.color(bg));
+    if style.reverse.unwrap_or_default() {
+        let reversed_fg = match bg == egui::Color32::TRANSPARENT {
+            true => theme.background,
+            false => bg,
+        };
+        (fg, bg) = (reversed_fg, fg);
+    }
+
+    let family = match (style.bold.unwrap_or_default(), style.monospace.unwrap_or_default()) {
+        (false, false) => egui::FontFamily::Proportional,
+        (false, true) => egui::FontFamily::Monospace,
+        (true, false) => egui::FontFamily::Name(BOLD_FAMILY.into()),
+        (true, true) => egui::FontFamily::Name(BOLD_MONOSPACE_FAMILY.into()),
+    };
+
+    TextFormat {
+        font_id: egui::FontId::new(text_size.font_size, family),
+        line_height: Some(text_size.line_height),
+        color: fg,
+        background: bg,
+        italics: style.italic.unwrap_or_default(),
+        underline: match style.underline.unwrap_or_default() {
+            true => egui::Stroke::new(2.0, fg),
+            false => egui::Stroke::NONE,
+        },
+        ..default_format()
     }
 }
 
@@ -268,14 +437,14 @@ impl Write for ColorFormatter {
         for line in data.split_inclusive(|b| *b == b'\n') {
             if line.ends_with(b"\n") {
                 self.write_new_style()?;
-                write_sanitized(&mut self.output, &line[..line.len() - 1])?;
+                self.write_text(&line[..line.len() - 1])?;
                 let labels = std::mem::take(&mut self.labels);
                 self.write_new_style()?;
-                self.output.write_all(b"\n")?;
+                self.new_line();
                 self.labels = labels;
             } else {
                 self.write_new_style()?;
-                write_sanitized(&mut self.output, line)?;
+                self.write_text(line)?;
             }
         }
 
@@ -288,36 +457,25 @@ impl Write for ColorFormatter {
     }
 }
 
-// const NOTABLE_LABELS: &[&str] = &["bookmarks", "description"];
-// const NOTABLE_LABELS: &[&str] = &["bookmarks"];
-const NOTABLE_LABELS: &[&[&str]] = &[&["bookmarks", "name"], &["description"]];
-
 impl Formatter for ColorFormatter {
     fn raw(&mut self) -> io::Result<Box<dyn Write + '_>> {
         self.write_new_style()?;
-        Ok(Box::new(self.output.by_ref()))
+        Ok(Box::new(RawWriter(self)))
     }
 
     fn push_label(&mut self, label: &str) -> io::Result<()> {
         self.labels.push(label.to_owned());
 
-        if let Some(head) = NOTABLE_LABELS
-            .iter()
-            .find(|&head| self.labels.iter().rev().zip(head.iter().rev()).all(|(a, b)| a == b))
-        {
-            self.flush_to_egui();
-            self.egui_output.push((LayoutJob::default(), Some(head[0].to_owned())));
+        if let Some(head) = self.notable_labels.iter().find(|&head| self.labels.ends_with(head)) {
+            let label = head.join(" ");
+            self.push_section(Some(label));
         }
         Ok(())
     }
 
     fn pop_label(&mut self) -> io::Result<()> {
-        if NOTABLE_LABELS
-            .iter()
-            .any(|&head| self.labels.iter().rev().zip(head.iter().rev()).all(|(a, b)| a == b))
-        {
-            self.flush_to_egui();
-            self.egui_output.push((LayoutJob::default(), None));
+        if self.notable_labels.iter().any(|head| self.labels.ends_with(head)) {
+            self.push_section(None);
         }
 
         self.labels.pop();
@@ -328,6 +486,34 @@ impl Formatter for ColorFormatter {
     }
 }
 
+/// Writes raw escape sequences, turning OSC 8 hyperlinks into [`Section::link`]s.
+struct RawWriter<'a>(&'a mut ColorFormatter);
+
+impl Write for RawWriter<'_> {
+    fn write(&mut self, data: &[u8]) -> Result<usize, Error> {
+        match parse_hyperlink(data) {
+            Some(url) => self.0.set_link((!url.is_empty()).then(|| url.to_owned())),
+            None => self.0.output.write_all(data)?,
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Parses the target of an OSC 8 hyperlink escape like `\e]8;;https://example.com\e\\`.
+///
+/// An empty target closes the current hyperlink.
+fn parse_hyperlink(data: &[u8]) -> Option<&str> {
+    let rest = data.strip_prefix(b"\x1b]8;")?;
+    let rest = rest.strip_suffix(b"\x1b\\").or_else(|| rest.strip_suffix(b"\x07"))?;
+    // skip the parameters, e.g. `id=...`
+    let url = &rest[rest.iter().position(|&b| b == b';')? + 1..];
+    std::str::from_utf8(url).ok()
+}
+
 impl Drop for ColorFormatter {
     fn drop(&mut self) {
         // If a `ColorFormatter` was dropped without popping all labels first (perhaps
@@ -352,3 +538,152 @@ fn write_sanitized(output: &mut impl Write, buf: &[u8]) -> Result<(), Error> {
         output.write_all(buf)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jj_lib::config::{ConfigLayer, ConfigSource};
+
+    /// The format `text` is written with, inside the given labels and with the given `[colors]` config.
+    fn format_of(colors: &str, labels: &[&str]) -> TextFormat {
+        let mut config = StackedConfig::empty();
+        config.add_layer(ConfigLayer::parse(ConfigSource::User, colors).unwrap());
+        let mut formatter = ColorFormatter::for_config(&config, Vec::new(), AutoLinks::default(), false).unwrap();
+
+        for label in labels {
+            formatter.push_label(label).unwrap();
+        }
+        write!(formatter, "text").unwrap();
+        for _ in labels {
+            formatter.pop_label().unwrap();
+        }
+
+        let lines = formatter.take();
+        let job = &lines[0][0].job;
+        let section = job
+            .sections
+            .iter()
+            .find(|section| &job.text[section.byte_range.clone()] == "text")
+            .unwrap();
+        section.format.clone()
+    }
+
+    #[test]
+    fn fg_and_bg() {
+        let theme = ColorTheme::dark();
+        let format = format_of(r#"colors.a = { fg = "red", bg = "blue" }"#, &["a"]);
+        assert_eq!(format.color, theme.color(Color::DarkRed));
+        assert_eq!(format.background, theme.color(Color::DarkBlue));
+    }
+
+    #[test]
+    fn rules_merge_by_priority() {
+        let theme = ColorTheme::dark();
+        let colors = r#"
+            colors.a = { fg = "red", bg = "blue" }
+            colors."a b" = { fg = "green" }
+        "#;
+        let format = format_of(colors, &["a", "b"]);
+        assert_eq!(format.color, theme.color(Color::DarkGreen));
+        assert_eq!(format.background, theme.color(Color::DarkBlue));
+    }
+
+    #[test]
+    fn reverse_swaps_fg_and_bg() {
+        let theme = ColorTheme::dark();
+        let format = format_of(r#"colors.a = { fg = "red", bg = "blue", reverse = true }"#, &["a"]);
+        assert_eq!(format.color, theme.color(Color::DarkBlue));
+        assert_eq!(format.background, theme.color(Color::DarkRed));
+
+        // without a background, the text is drawn in the background color
+        let format = format_of(r#"colors.a = { fg = "red", reverse = true }"#, &["a"]);
+        assert_eq!(format.color, theme.background);
+        assert_eq!(format.background, theme.color(Color::DarkRed));
+    }
+
+    #[test]
+    fn ansi_256_colors() {
+        let theme = ColorTheme::dark();
+        let colors = r#"
+            colors.cube = "ansi-color-196"
+            colors.gray = "ansi-color-244"
+            colors.basic = "ansi-color-1"
+        "#;
+        assert_eq!(format_of(colors, &["cube"]).color, egui::Color32::from_rgb(255, 0, 0));
+        assert_eq!(format_of(colors, &["gray"]).color, egui::Color32::from_gray(128));
+        assert_eq!(format_of(colors, &["basic"]).color, theme.ansi[1]);
+    }
+
+    #[test]
+    fn bold() {
+        let format = format_of(r#"colors.a = { bold = true }"#, &["a"]);
+        assert_eq!(format.font_id.family, egui::FontFamily::Name(BOLD_FAMILY.into()));
+
+        let format = format_of(r#"colors.a = { bold = true }"#, &["b"]);
+        assert_eq!(format.font_id.family, egui::FontFamily::Proportional);
+
+        let format = format_of(r#"colors.change_id = { bold = true }"#, &["change_id"]);
+        assert_eq!(format.font_id.family, egui::FontFamily::Name(BOLD_MONOSPACE_FAMILY.into()));
+    }
+
+    #[test]
+    fn labels_only_on_first_line() {
+        let notable = vec![vec!["description".to_owned()]];
+        let mut formatter =
+            ColorFormatter::for_config(&StackedConfig::empty(), notable, AutoLinks::default(), false).unwrap();
+        formatter.push_label("description").unwrap();
+        write!(formatter, "subject\nbody\n").unwrap();
+        formatter.pop_label().unwrap();
+
+        let lines = formatter.take();
+        let labels: Vec<Vec<Option<&str>>> = lines
+            .iter()
+            .map(|line| {
+                line.iter()
+                    .filter(|section| !section.job.is_empty())
+                    .map(|section| section.label.as_deref())
+                    .collect()
+            })
+            .collect();
+        assert_eq!(labels, [vec![Some("description")], vec![None]]);
+    }
+
+    #[test]
+    fn debug_markers_stay_in_their_row() {
+        let mut formatter =
+            ColorFormatter::for_config(&StackedConfig::empty(), Vec::new(), AutoLinks::default(), true).unwrap();
+        fn row_text(formatter: &mut ColorFormatter) -> String {
+            let lines = formatter.take();
+            lines[0].iter().map(|section| section.job.text.as_str()).collect()
+        }
+
+        // left open, like after a failed replay
+        formatter.push_label("a").unwrap();
+        write!(formatter, "x").unwrap();
+        assert_eq!(row_text(&mut formatter), "<<a::x>>");
+
+        write!(formatter, "y").unwrap();
+        assert_eq!(row_text(&mut formatter), "y");
+    }
+
+    #[test]
+    fn description_body_is_linked() {
+        let mut config = StackedConfig::empty();
+        let links = r#"kahva.links = { '#(\d+)' = "https://example.com/$1" }"#;
+        config.add_layer(ConfigLayer::parse(ConfigSource::User, links).unwrap());
+        let notable = vec![vec!["description".to_owned()]];
+        let links = AutoLinks::from_config(&config).unwrap();
+        let mut formatter = ColorFormatter::for_config(&config, notable, links, false).unwrap();
+        formatter.push_label("description").unwrap();
+        write!(formatter, "fix #1\nfixes #2\n").unwrap();
+        formatter.pop_label().unwrap();
+
+        let lines = formatter.take();
+        let links: Vec<Vec<&str>> = lines
+            .iter()
+            .map(|line| line.iter().filter_map(|section| section.link.as_deref()).collect())
+            .collect();
+        // the subject stays the describe target
+        assert_eq!(links, [vec![], vec!["https://example.com/2"]]);
+    }
+}
@@ -0,0 +1,173 @@
+use chrono::{DateTime, Local};
+use color_eyre::{Report, Section, SectionExt};
+use jj_cli::template_parser::TemplateParseError;
+use jj_lib::fileset::FilesetParseError;
+use jj_lib::revset::RevsetParseError;
+use std::error::Error;
+use std::fmt::Display;
+
+/// How many errors are kept in the error log.
+const MAX_ERRORS: usize = 100;
+
+/// An error shown to the user, keeping the causes which `to_string` would drop.
+#[derive(Clone, Debug)]
+pub struct ErrorReport {
+    pub message: String,
+    /// The underlying errors, outermost first.
+    /// For parse errors of revsets and templates these point at the position in the input.
+    pub causes: Vec<String>,
+    /// Where a panic happened.
+    pub backtrace: Option<String>,
+    pub time: DateTime<Local>,
+}
+
+impl ErrorReport {
+    pub fn new(message: impl Display) -> ErrorReport {
+        ErrorReport {
+            message: message.to_string(),
+            causes: Vec::new(),
+            backtrace: None,
+            time: Local::now(),
+        }
+    }
+
+    /// The full report, like the CLI prints it.
+    pub fn full_text(&self) -> String {
+        let mut text = self.message.clone();
+        for cause in &self.causes {
+            text.push_str("\nCaused by: ");
+            text.push_str(cause);
+        }
+        if let Some(backtrace) = &self.backtrace {
+            text.push_str("\n\nBacktrace:\n");
+            text.push_str(backtrace);
+        }
+        text
+    }
+
+    /// Turns this back into a report with the causes as its chain, and the backtrace as a section
+    /// instead of being part of the message.
+    pub fn into_report(self) -> Report {
+        let mut texts = std::iter::once(self.message).chain(self.causes).rev();
+        let innermost = Report::msg(texts.next().unwrap_or_default());
+        let report = texts.fold(innermost, |report, text| report.wrap_err(text));
+        match self.backtrace {
+            Some(backtrace) => report.section(backtrace.header("Backtrace:")),
+            None => report,
+        }
+    }
+}
+
+impl From<Report> for ErrorReport {
+    fn from(report: Report) -> ErrorReport {
+        let mut chain = report.chain().flat_map(error_texts);
+        let message = chain.next().unwrap_or_default();
+        let mut causes: Vec<String> = Vec::new();
+        for cause in chain {
+            // wrapping errors often repeat their source at the end of their message
+            let suffix = format!(": {cause}");
+            if !message.ends_with(&suffix) && !causes.iter().any(|other| other.ends_with(&suffix)) {
+                causes.push(cause);
+            }
+        }
+        ErrorReport {
+            message,
+            causes,
+            backtrace: None,
+            time: Local::now(),
+        }
+    }
+}
+
+/// The message of an error. Parse errors display as pest's multi-line report, which is split into
+/// a one-line message and the offending input with the error position marked.
+fn error_texts(error: &(dyn Error + 'static)) -> Vec<String> {
+    let message = if let Some(err) = error.downcast_ref::<RevsetParseError>() {
+        format!("Failed to parse revset: {}", err.kind())
+    } else if let Some(err) = error.downcast_ref::<TemplateParseError>() {
+        format!("Failed to parse template: {}", err.kind())
+    } else if let Some(err) = error.downcast_ref::<FilesetParseError>() {
+        format!("Failed to parse fileset: {}", err.kind())
+    } else {
+        return vec![error.to_string()];
+    };
+    std::iter::once(message).chain(parse_error_span(&error.to_string())).collect()
+}
+
+/// The input line and the caret below it from a pest report like
+///
+/// ```text
+///  --> 1:5
+///   |
+/// 1 | foo(
+///   |     ^---
+///   |
+///   = expected <expression>
+/// ```
+fn parse_error_span(report: &str) -> Option<String> {
+    let mut lines = report
+        .lines()
+        .skip_while(|line| !line.trim_start().starts_with(|c: char| c.is_ascii_digit()));
+    let (_, input) = lines.next()?.split_once("| ")?;
+    let (_, marker) = lines.next()?.split_once("| ")?;
+    Some(format!("{input}\n{marker}"))
+}
+
+impl From<String> for ErrorReport {
+    fn from(message: String) -> ErrorReport {
+        ErrorReport::new(message)
+    }
+}
+
+/// The errors which occurred, the most recent last.
+/// New errors are shown as notifications until they are dismissed, and stay in the log afterwards.
+#[derive(Default)]
+pub struct ErrorLog {
+    reports: Vec<(ErrorReport, bool)>,
+}
+
+impl ErrorLog {
+    pub fn push(&mut self, report: ErrorReport) {
+        if self.reports.len() >= MAX_ERRORS {
+            self.reports.remove(0);
+        }
+        self.reports.push((report, false));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.reports.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.reports.len()
+    }
+
+    pub fn reports(&self) -> impl DoubleEndedIterator<Item = &ErrorReport> + ExactSizeIterator {
+        self.reports.iter().map(|(report, _)| report)
+    }
+
+    /// The errors which weren't dismissed yet, with their index in the log.
+    pub fn notifications(&self) -> impl Iterator<Item = (usize, &ErrorReport)> {
+        self.reports
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, dismissed))| !dismissed)
+            .map(|(index, (report, _))| (index, report))
+    }
+
+    pub fn dismiss(&mut self, index: usize) {
+        if let Some((_, dismissed)) = self.reports.get_mut(index) {
+            *dismissed = true;
+        }
+    }
+
+    pub fn dismiss_all(&mut self) {
+        for (_, dismissed) in &mut self.reports {
+            *dismissed = true;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.reports.clear();
+    }
+}
@@ -7,7 +7,7 @@ use jj_cli::commands::run;
 use jj_cli::commit_templater::{CommitTemplateLanguage, CommitTemplateLanguageExtension};
 use jj_cli::config::{ConfigEnv, config_from_environment, default_config_layers};
 use jj_cli::diff_util::{self, UnifiedDiffOptions, show_diff_summary};
-use jj_cli::formatter::Formatter;
+use jj_cli::formatter::{Formatter, PlainTextFormatter};
 use jj_cli::revset_util::{self, RevsetExpressionEvaluator};
 use jj_cli::template_builder::{self, TemplateLanguage};
 use jj_cli::template_parser::{TemplateAliasesMap, TemplateDiagnostics};
@@ -18,6 +18,11 @@ use jj_lib::commit::Commit;
 use jj_lib::config::{ConfigGetError, ConfigGetResultExt, ConfigNamePathBuf, StackedConfig};
 use jj_lib::conflicts::{ConflictMarkerStyle, MaterializedTreeDiffEntry, materialized_diff_stream};
 use jj_lib::copies::CopyRecords;
+use jj_lib::file_util;
+use jj_lib::fileset::{self, FilesetDiagnostics};
+use jj_lib::fsmonitor::FsmonitorSettings;
+use jj_lib::git_backend::GitBackend;
+use jj_lib::gitignore::GitIgnoreFile;
 use jj_lib::id_prefix::IdPrefixContext;
 use jj_lib::matchers::{EverythingMatcher, Matcher};
 use jj_lib::merged_tree::MergedTree;
@@ -28,21 +33,25 @@ use jj_lib::revset::{
     self, RevsetAliasesMap, RevsetDiagnostics, RevsetExpression, RevsetExtensions, RevsetIteratorExt, RevsetModifier,
     RevsetParseContext, RevsetWorkspaceContext, UserRevsetExpression,
 };
-use jj_lib::settings::UserSettings;
+use jj_lib::settings::{HumanByteSize, UserSettings};
 use jj_lib::str_util::StringPattern;
 use jj_lib::workspace::{DefaultWorkspaceLoaderFactory, Workspace, WorkspaceLoaderFactory};
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::sync::Arc;
+use std::time::SystemTime;
 
-use color_eyre::eyre::{Result, ensure, eyre};
+use color_eyre::eyre::{Result, bail, ensure, eyre};
 use jj_cli::cli_util::{CliRunner, CommandHelper, WorkspaceCommandEnvironment, find_workspace_dir};
 use jj_cli::command_error::CommandError;
 use jj_lib::backend::CommitId;
 use jj_lib::object_id::ObjectId;
-use jj_lib::op_store::RefTarget;
-use jj_lib::working_copy::{CheckoutOptions, CheckoutStats};
+use jj_lib::op_store::{OperationId, RefTarget};
+use jj_lib::op_walk;
+use jj_lib::operation::Operation;
+use jj_lib::transaction::Transaction;
+use jj_lib::working_copy::{CheckoutError, CheckoutOptions, CheckoutStats, SnapshotOptions, WorkingCopyFreshness};
 
 pub struct Repo {
     workspace: Workspace,
@@ -59,6 +68,23 @@ pub struct Repo {
 
     immutable_heads_expression: Rc<UserRevsetExpression>,
     command_helper: CommandHelper,
+    options: LoadOptions,
+    /// The operation the repo was loaded at, if it isn't the head operation.
+    at_operation: Option<OperationId>,
+    /// The config files with their modification times when the config was loaded.
+    config_files: Vec<(PathBuf, Option<SystemTime>)>,
+    working_copy_stale: bool,
+}
+
+/// How a [`Repo`] is loaded.
+#[derive(Clone, Default)]
+pub struct LoadOptions {
+    /// Don't snapshot or update the working copy, like jj's `--ignore-working-copy`.
+    pub ignore_working_copy: bool,
+    /// Load the repo as it was at this operation, read-only, like jj's `--at-operation`.
+    pub at_operation: Option<String>,
+    /// Allow rewriting immutable commits, like jj's `--ignore-immutable`.
+    pub ignore_immutable: bool,
 }
 
 pub struct DiffState<'a> {
@@ -77,18 +103,25 @@ impl Repo {
         self.workspace.workspace_root()
     }
 
-    pub fn detect_cwd() -> Result<Option<Repo>> {
-        Repo::detect(&std::env::current_dir()?)
+    /// The `.jj/repo` directory, which may be shared with other workspaces.
+    pub fn repo_path(&self) -> &Path {
+        self.workspace.repo_path()
+    }
+
+    pub fn detect_cwd(options: LoadOptions) -> Result<Option<Repo>> {
+        Repo::detect(&std::env::current_dir()?, options)
     }
 
-    pub fn detect(cwd: &Path) -> Result<Option<Repo>> {
+    pub fn detect(cwd: &Path, options: LoadOptions) -> Result<Option<Repo>> {
         let Some(workspace_dir) = Repo::find_root(cwd) else {
             return Ok(None);
         };
-        Repo::load_at(workspace_dir).map(Some)
+        Repo::load_at(workspace_dir, options).map(Some)
     }
 
-    pub fn load_at(workspace_dir: &Path) -> Result<Repo> {
+    /// Loads the repo at its head operation, snapshotting the working copy first
+    /// unless it is ignored, or read-only at a past operation.
+    pub fn load_at(workspace_dir: &Path, mut options: LoadOptions) -> Result<Repo> {
         let mut config_env = ConfigEnv::from_environment();
         let mut raw_config = config_from_environment(default_config_layers());
         config_env.reload_user_config(&mut raw_config)?;
@@ -108,6 +141,15 @@ impl Repo {
             &working_copy_factories,
         )?;
         let repo = workspace.repo_loader().load_at_head()?;
+        let (repo, at_operation) = match &options.at_operation {
+            Some(op_str) => {
+                let operation = op_walk::resolve_op_with_repo(&repo, op_str)?;
+                // relative operations like `@-` have to stay where they are on reload
+                options.at_operation = Some(operation.id().hex());
+                (repo.loader().load_at(&operation)?, Some(operation.id().clone()))
+            }
+            None => (repo, None),
+        };
         let path_converter = RepoPathUiConverter::Fs {
             cwd: workspace.workspace_root().to_owned(),
             base: workspace.workspace_root().to_owned(),
@@ -115,12 +157,17 @@ impl Repo {
 
         let ui = Ui::with_config(settings.config()).map_err(|e| e.error)?;
 
+        // the repo config may not exist yet
+        let repo_config_path = workspace.repo_path().join("config.toml");
+        let config_paths = settings.config().layers().iter().filter_map(|layer| layer.path.clone());
+        let config_files = config_file_mtimes(config_paths.chain([repo_config_path]));
+
         let revset_aliases_map = load_revset_aliases(settings.config())?;
         #[allow(clippy::arc_with_non_send_sync)]
         let revset_extensions = Arc::new(RevsetExtensions::new());
         // TODO(config): user disambiguator
+        // the disambiguation index is built lazily on first use
         let id_prefix_context = IdPrefixContext::new(Arc::clone(&revset_extensions));
-        id_prefix_context.populate(repo.base_repo())?;
 
         let template_aliases_map = load_template_aliases(settings.config())?;
 
@@ -140,6 +187,10 @@ impl Repo {
             template_aliases_map,
             immutable_heads_expression: RevsetExpression::root(),
             command_helper,
+            options,
+            at_operation,
+            config_files,
+            working_copy_stale: false,
         };
 
         this.immutable_heads_expression =
@@ -150,14 +201,220 @@ impl Repo {
             None => this.id_prefix_context,
         };
 
+        if !this.options.ignore_working_copy && this.at_operation.is_none() {
+            this.snapshot_working_copy()?;
+        }
+
         Ok(this)
     }
 
-    pub fn reload(&mut self) -> Result<()> {
-        *self = Repo::load_at(self.workspace_dir())?;
+    /// Reloads the repo at its new head operation.
+    ///
+    /// Everything derived from the config is only reloaded if one of the config files changed,
+    /// which is returned.
+    pub fn reload(&mut self) -> Result<bool> {
+        if config_file_mtimes(self.config_files.iter().map(|(path, _)| path.clone())) != self.config_files {
+            *self = Repo::load_at(self.workspace_dir(), self.options.clone())?;
+            return Ok(true);
+        }
+        if self.at_operation.is_some() {
+            // a past operation doesn't change
+            return Ok(false);
+        }
+
+        self.repo = self.repo.reload_at_head()?;
+        if !self.options.ignore_working_copy {
+            self.snapshot_working_copy()?;
+        }
+        Ok(false)
+    }
+
+    /// Loads the head operation and snapshots the working copy before modifying the repo, like jj commands do,
+    /// so that neither operations of other processes nor edits to the files are lost.
+    pub fn prepare_mutation(&mut self) -> Result<()> {
+        self.ensure_writable()?;
+        self.repo = self.repo.reload_at_head()?;
+        if !self.options.ignore_working_copy {
+            self.snapshot_working_copy()?;
+        }
+        Ok(())
+    }
+
+    /// The visible commit with the change id of `commit_id`, which was rewritten since it was loaded
+    /// if e.g. preparing a mutation snapshotted it or one of its ancestors.
+    fn latest_version(&self, commit_id: &CommitId) -> Result<Commit> {
+        let commit = self.commit(commit_id)?;
+        match self.repo.resolve_change_id(commit.change_id()).as_deref() {
+            Some([latest]) if latest != commit_id => self.commit(latest),
+            // divergent or abandoned commits are left as they are
+            _ => Ok(commit),
+        }
+    }
+
+    /// Loads the repo at a past operation, or at the head operation again for `None`.
+    pub fn set_at_operation(&mut self, at_operation: Option<String>) -> Result<()> {
+        let options = LoadOptions {
+            at_operation,
+            ..self.options.clone()
+        };
+        *self = Repo::load_at(self.workspace_dir(), options)?;
+        Ok(())
+    }
+
+    /// The operation the repo is browsed at, if it was loaded at a past operation.
+    pub fn at_operation(&self) -> Option<&OperationId> {
+        self.at_operation.as_ref()
+    }
+
+    fn ensure_writable(&self) -> Result<()> {
+        ensure!(
+            self.at_operation.is_none(),
+            "The repo is shown at a past operation and can't be modified"
+        );
+        Ok(())
+    }
+
+    /// Fails if any of the commits is immutable and may not be rewritten,
+    /// which is allowed for all but the root commit with `ignore_immutable`.
+    fn check_rewritable(&self, commit_ids: &[CommitId]) -> Result<()> {
+        let root_commit_id = self.repo.store().root_commit_id();
+        ensure!(
+            !commit_ids.contains(root_commit_id),
+            "The root commit {} is immutable",
+            &root_commit_id.hex()[..12]
+        );
+        if let Some(commit_id) = self.find_immutable_commit(self.repo.as_ref(), commit_ids)? {
+            bail!(
+                "Commit {} is immutable, configure the set of immutable commits via \
+                 `revset-aliases.\"immutable_heads()\"` or start kahva with `--ignore-immutable`",
+                &commit_id.hex()[..12]
+            );
+        }
+        Ok(())
+    }
+
+    /// The first of the commits which is immutable in `repo`, which can be the repo of a transaction.
+    ///
+    /// With `ignore_immutable`, no commit is treated as immutable.
+    fn find_immutable_commit(
+        &self,
+        repo: &dyn jj_lib::repo::Repo,
+        commit_ids: &[CommitId],
+    ) -> Result<Option<CommitId>> {
+        if self.options.ignore_immutable {
+            return Ok(None);
+        }
+        let expression = RevsetExpression::commits(commit_ids.to_vec()).intersection(&self.immutable_expression());
+        let evaluator = RevsetExpressionEvaluator::new(
+            repo,
+            Arc::clone(&self.revset_extensions),
+            &self.id_prefix_context,
+            expression,
+        );
+        let commit_id = evaluator.evaluate()?.iter().next().transpose()?;
+        Ok(commit_id)
+    }
+
+    /// The commits which aren't visible at the head operation anymore, e.g. because they were rewritten.
+    pub fn hidden_at_head(&self, commit_ids: Vec<CommitId>) -> Result<HashSet<CommitId>> {
+        let head_repo = self.repo.reload_at_head()?;
+        let visible = RevsetExpression::commits(head_repo.view().heads().iter().cloned().collect()).ancestors();
+        // the index at the head contains the commits of past operations as well
+        let hidden = RevsetExpression::commits(commit_ids).minus(&visible);
+        let hidden = hidden.evaluate(head_repo.as_ref())?.iter().collect::<Result<_, _>>()?;
+        Ok(hidden)
+    }
+
+    /// Records changes to the files of the working copy in the working-copy commit, like jj commands do.
+    fn snapshot_working_copy(&mut self) -> Result<()> {
+        let workspace_id = self.workspace.workspace_id().to_owned();
+        let Some(wc_commit_id) = self.repo.view().get_wc_commit_id(&workspace_id) else {
+            // the workspace was deleted
+            return Ok(());
+        };
+        let wc_commit = self.repo.store().get_commit(wc_commit_id)?;
+
+        let auto_tracking_matcher = self.auto_tracking_matcher()?;
+        let HumanByteSize(max_new_file_size) = self.settings.get("snapshot.max-new-file-size")?;
+        let options = SnapshotOptions {
+            base_ignores: self.base_ignores()?,
+            fsmonitor_settings: FsmonitorSettings::from_settings(&self.settings)?,
+            progress: None,
+            start_tracking_matcher: auto_tracking_matcher.as_ref(),
+            max_new_file_size,
+            conflict_marker_style: self.settings.get("ui.conflict-marker-style")?,
+        };
+
+        let mut locked_ws = self.workspace.start_working_copy_mutation()?;
+        let freshness = WorkingCopyFreshness::check_stale(locked_ws.locked_wc(), &wc_commit, &self.repo)?;
+        let (repo, wc_commit) = match freshness {
+            WorkingCopyFreshness::Fresh => (self.repo.clone(), wc_commit),
+            WorkingCopyFreshness::Updated(wc_operation) => {
+                // the working copy was updated by an operation which isn't visible at the loaded head
+                let repo = self.repo.reload_at(&wc_operation)?;
+                let Some(wc_commit_id) = repo.view().get_wc_commit_id(&workspace_id) else {
+                    return Ok(());
+                };
+                let wc_commit = repo.store().get_commit(wc_commit_id)?;
+                (repo, wc_commit)
+            }
+            // a stale working copy can't be snapshotted, it is shown as of its last snapshot
+            WorkingCopyFreshness::WorkingCopyStale | WorkingCopyFreshness::SiblingOperation => {
+                self.working_copy_stale = true;
+                return Ok(());
+            }
+        };
+        self.working_copy_stale = false;
+
+        let (new_tree_id, _stats) = locked_ws.locked_wc().snapshot(&options)?;
+        let repo = if new_tree_id != *wc_commit.tree_id() {
+            let mut tx = repo.start_transaction();
+            tx.set_is_snapshot(true);
+            let commit = tx
+                .repo_mut()
+                .rewrite_commit(&wc_commit)
+                .set_tree_id(new_tree_id)
+                .write()?;
+            tx.repo_mut().set_wc_commit(workspace_id, commit.id().clone())?;
+            tx.repo_mut().rebase_descendants()?;
+            tx.commit("snapshot working copy")?
+        } else {
+            repo
+        };
+        locked_ws.finish(repo.op_id().clone())?;
+        self.repo = repo;
+
         Ok(())
     }
 
+    /// Matches the new files which are tracked when snapshotting, configured by `snapshot.auto-track`.
+    fn auto_tracking_matcher(&self) -> Result<Box<dyn Matcher>> {
+        let pattern = self.settings.get_string("snapshot.auto-track")?;
+        let expression = fileset::parse(&mut FilesetDiagnostics::new(), &pattern, &self.path_converter)?;
+        Ok(expression.to_matcher())
+    }
+
+    /// Ignore rules applying in addition to the `.gitignore` files in the workspace:
+    /// the global excludes file and `info/exclude`, like jj's.
+    fn base_ignores(&self) -> Result<Arc<GitIgnoreFile>> {
+        let mut ignores = GitIgnoreFile::empty();
+        if let Some(git_backend) = self.repo.store().backend_impl().downcast_ref::<GitBackend>() {
+            let excludes_file = match git_backend.git_repo().config_snapshot().string("core.excludesFile") {
+                // relative paths are resolved against the work tree, like git does
+                Some(value) => std::str::from_utf8(&value)
+                    .ok()
+                    .map(|path| self.workspace_dir().join(file_util::expand_home_path(path))),
+                None => xdg_config_home().map(|config| config.join("git").join("ignore")),
+            };
+            if let Some(excludes_file) = excludes_file {
+                ignores = ignores.chain_with_file("", excludes_file)?;
+            }
+            let exclude = git_backend.git_repo_path().join("info").join("exclude");
+            ignores = ignores.chain_with_file("", exclude)?;
+        }
+        Ok(ignores)
+    }
+
     pub fn settings(&self) -> &UserSettings {
         &self.settings
     }
@@ -165,6 +422,11 @@ impl Repo {
         self.repo.as_ref()
     }
 
+    /// The loaded repo state, which is replaced by every reload or modification.
+    pub fn readonly_repo(&self) -> &Arc<ReadonlyRepo> {
+        &self.repo
+    }
+
     pub fn write_log(&self, f: &mut dyn Formatter, commit: &Commit) -> Result<()> {
         let language = self.commit_template_language();
         let template_string = self.settings.get_string("templates.log")?;
@@ -258,7 +520,9 @@ impl Repo {
         Ok(commit)
     }
 
-    pub fn move_bookmark(&mut self, bookmark: &RefName, to: &CommitId) -> Result<()> {
+    pub fn move_bookmark(&mut self, bookmark: &RefName, to: &CommitId) -> Result<Vec<String>> {
+        self.prepare_mutation()?;
+        let to = self.latest_version(to)?.id().clone();
         let mut tx = self.repo.start_transaction();
         tx.set_tag("bookmark".to_owned(), bookmark.as_str().to_owned());
 
@@ -269,14 +533,11 @@ impl Repo {
             bookmark.as_str()
         );
 
-        tx.repo_mut()
-            .set_local_bookmark_target(bookmark, RefTarget::normal(to.clone()));
+        tx.repo_mut().set_local_bookmark_target(bookmark, RefTarget::normal(to));
 
         jj_lib::git::export_refs(tx.repo_mut())?;
 
-        self.repo = tx.commit("kahva: move bookmark")?;
-
-        Ok(())
+        self.finish_transaction(tx, "kahva: move bookmark")
     }
 
     fn git_push_bookmark(&mut self, pattern: StringPattern) -> Result<()> {
@@ -298,32 +559,46 @@ impl Repo {
         Ok(())
     }
 
-    pub fn describe(&mut self, commit: &Commit, description: &str) -> Result<()> {
+    /// The text to edit the description of a commit in, rendered from `templates.draft_commit_description`.
+    pub fn draft_description(&self, commit: &Commit) -> Result<String> {
+        let template = self.settings_commit_template("templates.draft_commit_description")?;
+        let mut output = Vec::new();
+        template.format(commit, &mut PlainTextFormatter::new(&mut output))?;
+        Ok(String::from_utf8_lossy(&output).into_owned())
+    }
+
+    pub fn describe(&mut self, commit_id: &CommitId, description: &str) -> Result<Vec<String>> {
+        self.prepare_mutation()?;
+        let commit = self.latest_version(commit_id)?;
+        self.check_rewritable(&[commit.id().clone()])?;
         let mut tx = self.repo.start_transaction();
         tx.set_tag("commit".to_owned(), commit.change_id().to_string());
 
         tx.repo_mut()
-            .rewrite_commit(commit)
+            .rewrite_commit(&commit)
             .set_description(description)
             .write()?;
         let num_changed = tx.repo_mut().rebase_descendants()?;
 
-        /*for (workspace_id, wc_commit_id) in &tx.repo().view().wc_commit_ids().clone() {
-            if self
-                .env
-                .find_immutable_commit(tx.repo(), [wc_commit_id])?
-                .is_some()
-            {
+        self.finish_transaction(tx, "kahva: describe")
+    }
+
+    /// Commits the transaction, updating the working copy if the working-copy commit changed.
+    ///
+    /// Returns notices about what happened to the working copies besides the operation itself.
+    fn finish_transaction(&mut self, mut tx: Transaction, description: &str) -> Result<Vec<String>> {
+        let mut notices = Vec::new();
+        for (workspace_id, wc_commit_id) in &tx.repo().view().wc_commit_ids().clone() {
+            if self.find_immutable_commit(tx.repo(), &[wc_commit_id.clone()])?.is_some() {
                 let wc_commit = tx.repo().store().get_commit(wc_commit_id)?;
                 tx.repo_mut().check_out(workspace_id.clone(), &wc_commit)?;
-                writeln!(
-                    ui.warning_default(),
-                    "The working-copy commit in workspace '{}' became immutable, so a new commit \
-                     has been created on top of it.",
+                notices.push(format!(
+                    "The working-copy commit in workspace '{}' became immutable, \
+                     so a new commit has been created on top of it.",
                     workspace_id.as_str()
-                )?;
+                ));
             }
-        }*/
+        }
 
         let old_repo = tx.base_repo();
         let maybe_old_wc_commit = old_repo
@@ -338,28 +613,117 @@ impl Repo {
             .map(|commit_id| tx.repo().store().get_commit(commit_id))
             .transpose()?;
 
-        // if self.may_update_working_copy {
-        if let Some(new_commit) = &maybe_new_wc_commit {
-            // self.update_working_copy(ui, maybe_old_wc_commit.as_ref(), new_commit)?;
-            let checkout_options = CheckoutOptions {
-                conflict_marker_style: self.settings.get("ui.conflict-marker-style")?,
-            };
+        self.repo = tx.commit(description)?;
+
+        // the working copy is updated to the new operation, otherwise it would be stale
+        let Some(new_commit) = &maybe_new_wc_commit else {
+            // the workspace was deleted
+            return Ok(notices);
+        };
+        if self.options.ignore_working_copy || maybe_old_wc_commit.as_ref() == Some(new_commit) {
+            return Ok(notices);
+        }
+        let checkout = self.checkout_options().and_then(|options| {
             update_working_copy(
                 &self.repo,
                 &mut self.workspace,
                 maybe_old_wc_commit.as_ref(),
                 new_commit,
-                &checkout_options,
-            );
-        } else {
-            // It seems the workspace was deleted, so we shouldn't try to
-            // update it.
+                &options,
+            )
+        });
+        match checkout {
+            Ok(stats) => notices.extend(format_checkout_stats(&stats)),
+            // the operation is committed already, so it didn't fail
+            Err(report) => notices.push(format!("{report:#}")),
         }
-        // }
 
-        self.repo = tx.commit("kahva: describe")?;
+        Ok(notices)
+    }
+
+    fn checkout_options(&self) -> Result<CheckoutOptions> {
+        Ok(CheckoutOptions {
+            conflict_marker_style: self.settings.get("ui.conflict-marker-style")?,
+        })
+    }
 
-        Ok(())
+    /// Whether the working copy couldn't be snapshotted on the last reload because it is stale,
+    /// e.g. after an operation in another workspace rewrote its commit.
+    pub fn is_working_copy_stale(&self) -> bool {
+        self.working_copy_stale
+    }
+
+    /// Updates a stale working copy to its working-copy commit, like `jj workspace update-stale`.
+    pub fn update_stale(&mut self) -> Result<Vec<String>> {
+        self.ensure_writable()?;
+        let workspace_id = self.workspace.workspace_id().to_owned();
+        let wc_commit_id = self
+            .repo
+            .view()
+            .get_wc_commit_id(&workspace_id)
+            .ok_or_else(|| eyre!("The workspace has no working-copy commit"))?;
+        let wc_commit = self.repo.store().get_commit(wc_commit_id)?;
+        let checkout_options = self.checkout_options()?;
+
+        let mut locked_ws = self.workspace.start_working_copy_mutation()?;
+        let stats = match WorkingCopyFreshness::check_stale(locked_ws.locked_wc(), &wc_commit, &self.repo)? {
+            WorkingCopyFreshness::WorkingCopyStale => locked_ws
+                .locked_wc()
+                .check_out(&wc_commit, &checkout_options)
+                .map_err(|err| checkout_error(err, &wc_commit))?,
+            WorkingCopyFreshness::SiblingOperation => {
+                bail!("The working copy was updated by an operation which isn't an ancestor of the repo's operation")
+            }
+            WorkingCopyFreshness::Fresh | WorkingCopyFreshness::Updated(_) => {
+                return Ok(vec!["The working copy is not stale".to_owned()]);
+            }
+        };
+        locked_ws.finish(self.repo.op_id().clone())?;
+        self.working_copy_stale = false;
+
+        let mut notices = vec![format!("Updated the working copy to {}", &wc_commit.id().hex()[..12])];
+        notices.extend(format_checkout_stats(&stats));
+        self.snapshot_working_copy()?;
+        Ok(notices)
+    }
+
+    /// The id of the operation the repo is loaded at.
+    pub fn operation_id(&self) -> &OperationId {
+        self.repo.op_id()
+    }
+
+    pub fn operation(&self, op_id: &OperationId) -> Result<Operation> {
+        Ok(self.repo.loader().load_operation(op_id)?)
+    }
+
+    /// The most recent operations, newest first.
+    pub fn operation_log(&self, limit: usize) -> Result<Vec<Operation>> {
+        let head = self.repo.operation().clone();
+        let operations = op_walk::walk_ancestors(&[head]).take(limit).collect::<Result<_, _>>()?;
+        Ok(operations)
+    }
+
+    /// The repo as it was after the given operation.
+    pub fn load_at_operation(&self, op_id: &OperationId) -> Result<Arc<ReadonlyRepo>> {
+        Ok(self.repo.loader().load_at(&self.operation(op_id)?)?)
+    }
+
+    /// Applies the changes between the repo states at two operations on top of the current state,
+    /// e.g. from an operation to its parent to undo it like `jj undo` does.
+    pub fn restore_operation(
+        &mut self,
+        from: &OperationId,
+        to: &OperationId,
+        description: &str,
+    ) -> Result<Vec<String>> {
+        self.ensure_writable()?;
+        let loader = self.repo.loader();
+        let from_repo = loader.load_at(&loader.load_operation(from)?)?;
+        let to_repo = loader.load_at(&loader.load_operation(to)?)?;
+
+        let mut tx = self.repo.start_transaction();
+        tx.repo_mut().merge(&from_repo, &to_repo)?;
+        self.finish_transaction(tx, description)
     }
 
     pub fn diff(&self, commit: &Commit) -> Result<DiffState<'_>> {
@@ -575,6 +939,16 @@ pub(super) fn evaluate_revset_to_single_commit(
     }
 }
 
+fn config_file_mtimes(paths: impl IntoIterator<Item = PathBuf>) -> Vec<(PathBuf, Option<SystemTime>)> {
+    paths
+        .into_iter()
+        .map(|path| {
+            let mtime = std::fs::metadata(&path).and_then(|metadata| metadata.modified()).ok();
+            (path, mtime)
+        })
+        .collect()
+}
+
 fn update_working_copy(
     repo: &Arc<ReadonlyRepo>,
     workspace: &mut Workspace,
@@ -583,10 +957,46 @@ fn update_working_copy(
     options: &CheckoutOptions,
 ) -> Result<CheckoutStats> {
     let old_tree_id = old_commit.map(|commit| commit.tree_id().clone());
-    // TODO: CheckoutError::ConcurrentCheckout should probably just result in a
-    // warning for most commands (but be an error for the checkout command)
     let stats = workspace
         .check_out(repo.op_id().clone(), old_tree_id.as_ref(), new_commit, options)
-        .map_err(|err| eyre!("Failed to check out commit {}", new_commit.id().hex()))?;
+        .map_err(|err| checkout_error(err, new_commit))?;
     Ok(stats)
 }
+
+/// `$XDG_CONFIG_HOME`, falling back to `~/.config`.
+fn xdg_config_home() -> Option<PathBuf> {
+    match std::env::var("XDG_CONFIG_HOME") {
+        Ok(dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => std::env::var("HOME").ok().map(|home| Path::new(&home).join(".config")),
+    }
+}
+
+fn checkout_error(err: CheckoutError, commit: &Commit) -> color_eyre::Report {
+    let commit_id = &commit.id().hex()[..12];
+    match err {
+        CheckoutError::ConcurrentCheckout => eyre!(
+            "The operation succeeded, but the working copy couldn't be updated to {commit_id} \
+             because it was updated concurrently. Update the stale working copy to recover."
+        ),
+        err => color_eyre::Report::new(err)
+            .wrap_err(format!("The operation succeeded, but checking out {commit_id} failed")),
+    }
+}
+
+/// Describes the changes to the files of the working copy, like jj does after a checkout.
+fn format_checkout_stats(stats: &CheckoutStats) -> Vec<String> {
+    let mut lines = Vec::new();
+    if stats.added_files > 0 || stats.updated_files > 0 || stats.removed_files > 0 {
+        lines.push(format!(
+            "Added {} files, modified {} files, removed {} files",
+            stats.added_files, stats.updated_files, stats.removed_files
+        ));
+    }
+    if stats.skipped_files > 0 {
+        lines.push(format!(
+            "{} of those updates were skipped because there were conflicting changes in the working copy",
+            stats.skipped_files
+        ));
+    }
+    lines
+}
@@ -0,0 +1,1526 @@
+use crate::backend::{CommitNode, ElidedEdge, RepoView, ViewOptions};
+use crate::describe::DescribeLints;
+use crate::egui_formatter::{Section, TextSize};
+use crate::errors::{ErrorLog, ErrorReport};
+use crate::links::AutoLinks;
+use crate::oplog::{CommitSummary, OperationDiff, OperationEntry};
+use crate::spans::{InteractiveSpans, SpanAction};
+use crate::theme::{ANSI_NAMES, ColorTheme, Themes};
+use crate::watcher::RepoWatcher;
+use crate::worker::{Request, Response, Worker};
+use clap::Parser;
+use color_eyre::Result;
+use color_eyre::eyre::eyre;
+use eframe::egui::{self, Color32, Theme};
+use egui::epaint::{ColorMode, CubicBezierShape, PathStroke};
+use egui::text::LayoutJob;
+use egui::{DragAndDrop, FontId, Margin, Pos2, Rect, RichText, Stroke, StrokeKind, TextEdit, TextStyle, Vec2, Widget};
+use jj_lib::backend::CommitId;
+use jj_lib::config::ConfigGetResultExt;
+use jj_lib::object_id::ObjectId;
+use jj_lib::op_store::OperationId;
+use jj_lib::ref_name::RefNameBuf;
+use jj_lib::settings::UserSettings;
+use renderdag::{LinkLine, NodeLine};
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+pub mod backend;
+mod describe;
+mod egui_formatter;
+mod errors;
+pub mod jj;
+mod links;
+mod oplog;
+mod panics;
+mod spans;
+mod theme;
+mod watcher;
+mod worker;
+
+#[derive(Clone, clap::Parser)]
+pub struct Args {
+    #[arg(long, default_value = ".")]
+    repository: PathBuf,
+    #[arg(short = 'r', long, value_name = "REVSETS")]
+    revisions: Option<String>,
+    /// Show the template labels and matching color rules when hovering text
+    #[arg(long)]
+    color_debug: bool,
+    /// Don't snapshot the working copy on reload, and don't update it after changing commits
+    #[arg(long)]
+    ignore_working_copy: bool,
+    /// Show the repo as it was at this operation, read-only
+    #[arg(long, visible_alias = "at-op", value_name = "OPERATION")]
+    at_operation: Option<String>,
+    /// Allow rewriting immutable commits
+    #[arg(long)]
+    ignore_immutable: bool,
+}
+
+pub fn run() -> Result<()> {
+    let mut args = Args::parse();
+    color_eyre::install()?;
+    panics::install_hook();
+    args.repository = std::path::absolute(&args.repository)?;
+
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default().with_inner_size([1200., 400.]),
+        ..Default::default()
+    };
+    let app = App::load(args)?;
+    eframe::run_native(
+        "kahva",
+        options,
+        Box::new(|cc| {
+            let mut app = app;
+            app.init(&cc.egui_ctx, cc.storage)?;
+            Ok(Box::new(app))
+        }),
+    )
+    .map_err(|e| eyre!("{e}"))?;
+    Ok(())
+}
+
+fn setup_custom_style(ctx: &egui::Context, settings: &UserSettings, style: &AppStyle, themes: &Themes) -> Result<()> {
+    let mut fonts = egui::FontDefinitions::default();
+    egui_formatter::add_bold_fonts(&mut fonts, settings.config())?;
+    ctx.set_fonts(fonts);
+
+    apply_style(ctx, style, themes);
+    Ok(())
+}
+
+fn apply_style(ctx: &egui::Context, app_style: &AppStyle, themes: &Themes) {
+    ctx.set_pixels_per_point(app_style.pixels_per_point);
+    for theme in [Theme::Dark, Theme::Light] {
+        let panel_fill = themes.get(theme).background;
+        ctx.style_mut_of(theme, |style| {
+            // style.visuals.panel_fill = Color32::from_rgb(11, 11, 22);
+            style.visuals.panel_fill = panel_fill;
+            *style.text_styles.get_mut(&TextStyle::Body).unwrap() = FontId::proportional(app_style.font_size);
+            style.interaction.selectable_labels = false;
+            // style.debug.show_widget_hits = true;
+        });
+    }
+}
+
+pub struct App(UiState, RepoView);
+impl App {
+    pub fn load(args: Args) -> Result<App> {
+        let debug = args.color_debug;
+        let (worker, settings, at_operation) = Worker::spawn(args)?;
+        let view_options = ViewOptions {
+            limit: Some(backend::PAGE_SIZE),
+            ..Default::default()
+        };
+
+        let spans = InteractiveSpans::from_config(settings.config())?;
+        let links = AutoLinks::from_config(settings.config())?;
+        let describe_lints = DescribeLints::from_config(settings.config())?;
+        let style = AppStyle::from_settings(&settings)?;
+        Ok(App(
+            UiState {
+                formatter: egui_formatter::ColorFormatter::for_config(
+                    settings.config(),
+                    spans.label_paths(),
+                    links,
+                    debug,
+                )?,
+                spans,
+                describe_lints,
+                describe_editor: None,
+                formatted_rows: Vec::new(),
+                log_lines: Vec::new(),
+                themes: Themes::from_config(settings.config())?,
+                worker,
+                settings,
+                watcher: None,
+                style,
+                view_options,
+                scroll_to: None,
+                toast: None,
+                oplog_open: false,
+                operations: Vec::new(),
+                selected_operation: None,
+                operation_diff: None,
+                at_operation,
+                highlight_changed: true,
+                custom_settings: false,
+                settings_open: false,
+                errors: ErrorLog::default(),
+                error_log_open: false,
+                initial_sized: false,
+                dirty: false,
+                loading_page: false,
+                // the first view is loaded in the background as well
+                view_dirty: true,
+            },
+            RepoView::default(),
+        ))
+    }
+
+    /// Applies the persisted settings and the style, and starts watching the repo.
+    pub fn init(&mut self, ctx: &egui::Context, storage: Option<&dyn eframe::Storage>) -> Result<()> {
+        if let Some(storage) = storage {
+            self.0.load_settings(storage);
+        }
+        setup_custom_style(ctx, &self.0.settings, &self.0.style, &self.0.themes)?;
+        let res = self.0.watch_repo(ctx);
+        self.0.catch(res);
+        Ok(())
+    }
+
+    /// Loads the whole revset instead of the first pages.
+    pub fn load_all(&mut self) {
+        self.0.load_all();
+    }
+
+    /// Whether a reload was requested which hasn't arrived yet.
+    pub fn is_loading(&self) -> bool {
+        self.0.dirty || self.0.view_dirty || self.0.worker.is_busy()
+    }
+
+    /// Handles the responses of the worker and draws the UI.
+    pub fn frame(&mut self, ctx: &egui::Context) {
+        for response in self.0.worker.poll() {
+            match response {
+                Response::View {
+                    view,
+                    reloaded,
+                    settings,
+                } => {
+                    self.1 = view;
+                    self.0.loading_page = false;
+                    self.0.formatted_rows.clear();
+                    self.0.log_lines.clear();
+                    if let Some(settings) = settings {
+                        let res = self.0.apply_settings(ctx, settings);
+                        self.0.catch(res);
+                    }
+                    if reloaded && self.0.oplog_open {
+                        self.0.request(Request::LoadOperations);
+                    }
+                }
+                Response::Page(page) => {
+                    let view = &mut self.1;
+                    view.nodes.extend(page.nodes);
+                    view.parents.extend(page.parents);
+                    view.heads.extend(page.heads);
+                    view.changed_since.extend(page.changed_since);
+                    view.has_more = page.has_more;
+                    view.working_copy_stale = page.working_copy_stale;
+                    self.0.loading_page = false;
+                    // the rows which were formatted already stay the same
+                    self.0.log_lines.clear();
+                }
+                Response::Cancelled => {}
+                Response::Changed {
+                    message,
+                    undone,
+                    operation,
+                } => {
+                    if let Some(watcher) = &mut self.0.watcher {
+                        watcher.ignore_operation(operation);
+                    }
+                    self.0.reload();
+                    self.0.toast = Some(Toast {
+                        message,
+                        undone,
+                        shown_at: Instant::now(),
+                    });
+                }
+                Response::Resolved(commit_id) => self.0.scroll_to = Some(commit_id),
+                Response::Description { commit_id, text } => {
+                    if let Some(editor) = &mut self.0.describe_editor {
+                        if editor.commit_id == commit_id {
+                            editor.original.get_or_insert_with(|| describe::cleanup_description(&text));
+                            editor.text = Some(text);
+                        }
+                    }
+                }
+                Response::AtOperation(op_id) => {
+                    self.0.at_operation = op_id;
+                    self.0.toast = None;
+                    self.0.reload();
+                }
+                Response::Operations(operations) => self.0.operations = operations,
+                Response::OperationDiff(diff) => {
+                    if self.0.selected_operation.as_ref() == Some(&diff.id) {
+                        self.0.operation_diff = Some(diff);
+                    }
+                }
+                Response::Error(report) => {
+                    self.0.loading_page = false;
+                    self.0.show_error(report);
+                }
+            }
+        }
+
+        // don't reload while a description is being edited, its widget could go away
+        if let Some(watcher) = &mut self.0.watcher {
+            if !ctx.wants_keyboard_input() && watcher.poll(ctx) {
+                self.0.dirty = true;
+            }
+        }
+
+        if self.0.dirty || self.0.view_dirty {
+            let res = self.0.worker.reload(self.0.dirty, self.0.view_options.clone());
+            self.0.catch(res);
+            self.0.dirty = false;
+            self.0.view_dirty = false;
+        }
+        if self.0.worker.is_busy() {
+            // keep polling for the response
+            ctx.request_repaint_after(Duration::from_millis(50));
+        }
+
+        self.0.update(ctx, &self.1)
+    }
+}
+
+struct UiState {
+    worker: Worker,
+    /// The settings of the repo, updated when it is reloaded.
+    settings: UserSettings,
+    /// Notices operations by other jj processes, unless `kahva.auto-reload` is disabled.
+    watcher: Option<RepoWatcher>,
+    formatter: egui_formatter::ColorFormatter,
+    spans: InteractiveSpans,
+    describe_lints: DescribeLints,
+    describe_editor: Option<DescribeEditor>,
+    /// The formatted sections of each node in the view, by index.
+    /// Cleared when the view or anything affecting the formatting changes.
+    formatted_rows: Vec<Option<Arc<[Vec<Section>]>>>,
+    /// The lines of the log, laid out once per view so only the visible ones are drawn.
+    log_lines: Vec<LogLine>,
+    themes: Themes,
+    style: AppStyle,
+    view_options: ViewOptions,
+    /// Commit to scroll to once its row is drawn.
+    scroll_to: Option<CommitId>,
+    /// Tells what the last operation did, offering to undo it.
+    toast: Option<Toast>,
+    oplog_open: bool,
+    operations: Vec<OperationEntry>,
+    selected_operation: Option<OperationId>,
+    operation_diff: Option<OperationDiff>,
+    /// The past operation the repo is browsed at, read-only.
+    at_operation: Option<OperationId>,
+    /// Whether commits which changed since `at_operation` are highlighted.
+    highlight_changed: bool,
+    /// Whether `style` and `themes` were changed in the settings window and should be persisted.
+    custom_settings: bool,
+    settings_open: bool,
+
+    errors: ErrorLog,
+    error_log_open: bool,
+
+    initial_sized: bool,
+    dirty: bool,
+    /// A page requested by scrolling to the end of the log hasn't arrived yet.
+    loading_page: bool,
+    /// Only the graph needs to be reloaded, e.g. after loading another page.
+    view_dirty: bool,
+}
+
+impl UiState {
+    fn describe(&mut self, commit_id: &CommitId, description: &str) -> Result<()> {
+        self.worker.send(Request::Describe {
+            commit_id: commit_id.clone(),
+            description: description.to_owned(),
+        })
+    }
+    fn reload(&mut self) {
+        self.dirty = true;
+        self.clear_error();
+    }
+
+    fn clear_error(&mut self) {
+        self.errors.dismiss_all();
+    }
+
+    fn read_only(&self) -> bool {
+        self.at_operation.is_some()
+    }
+
+    fn load_more(&mut self) {
+        self.view_options.limit = self.view_options.limit.map(|limit| limit + backend::PAGE_SIZE);
+        self.view_dirty = true;
+        self.loading_page = true;
+    }
+    fn load_all(&mut self) {
+        self.view_options.limit = None;
+        self.view_dirty = true;
+    }
+
+    fn expand_elided(&mut self, edge: &ElidedEdge) {
+        self.view_options.expanded_edges.push(edge.clone());
+        self.view_dirty = true;
+    }
+    fn collapse_elided(&mut self, edge: &ElidedEdge) {
+        self.view_options.expanded_edges.retain(|expanded| expanded != edge);
+        self.view_dirty = true;
+    }
+
+    fn expand_stack(&mut self, stack: &[CommitId]) {
+        self.view_options.expanded_stacks.insert(stack[0].clone());
+        self.view_dirty = true;
+    }
+
+    fn collapse_stack(&mut self, commit_id: &CommitId) {
+        self.view_options.expanded_stacks.remove(commit_id);
+        self.view_dirty = true;
+    }
+
+    fn set_filter(&mut self, filter: Option<String>) {
+        self.view_options.filter = filter;
+        self.view_dirty = true;
+    }
+
+    fn watch_repo(&mut self, ctx: &egui::Context) -> Result<()> {
+        let auto_reload = self.settings.get_bool("kahva.auto-reload").optional()?.unwrap_or(true);
+        if auto_reload {
+            self.watcher = Some(RepoWatcher::new(self.worker.repo_path(), ctx.clone())?);
+        }
+        Ok(())
+    }
+
+    fn request(&mut self, request: Request) {
+        let res = self.worker.send(request);
+        self.catch(res);
+    }
+
+    fn jump_to(&mut self, revision: &str) -> Result<()> {
+        self.worker.send(Request::Resolve(revision.to_owned()))
+    }
+
+    fn load_settings(&mut self, storage: &dyn eframe::Storage) {
+        if let Some(Some(style)) = eframe::get_value(storage, STYLE_KEY) {
+            self.style = style;
+            self.custom_settings = true;
+        }
+        if let Some(Some(themes)) = eframe::get_value(storage, THEMES_KEY) {
+            self.themes = themes;
+            self.custom_settings = true;
+        }
+    }
+
+    fn save_settings(&self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, STYLE_KEY, &self.custom_settings.then_some(&self.style));
+        eframe::set_value(storage, THEMES_KEY, &self.custom_settings.then_some(&self.themes));
+    }
+
+    /// Rebuilds everything derived from the jj config after it changed,
+    /// apart from the style and themes if they were customized in the settings window.
+    fn apply_settings(&mut self, ctx: &egui::Context, settings: UserSettings) -> Result<()> {
+        let spans = InteractiveSpans::from_config(settings.config())?;
+        let links = AutoLinks::from_config(settings.config())?;
+        let formatter = egui_formatter::ColorFormatter::for_config(
+            settings.config(),
+            spans.label_paths(),
+            links,
+            self.formatter.is_debug(),
+        )?;
+        self.describe_lints = DescribeLints::from_config(settings.config())?;
+        if !self.custom_settings {
+            self.style = AppStyle::from_settings(&settings)?;
+            self.themes = Themes::from_config(settings.config())?;
+        }
+        self.formatter = formatter;
+        self.spans = spans;
+        self.settings = settings;
+        self.formatted_rows.clear();
+        setup_custom_style(ctx, &self.settings, &self.style, &self.themes)?;
+
+        // `kahva.auto-reload` may have changed
+        self.watcher = None;
+        self.watch_repo(ctx)
+    }
+
+    /// Discards the settings from the settings window, going back to the jj config.
+    fn reset_settings(&mut self) -> Result<()> {
+        self.style = AppStyle::from_settings(&self.settings)?;
+        self.themes = Themes::from_config(self.settings.config())?;
+        self.custom_settings = false;
+        Ok(())
+    }
+}
+
+const STYLE_KEY: &str = "style";
+const THEMES_KEY: &str = "themes";
+
+/// The height of a line of text relative to the font size, including the gap to the next line.
+const LINE_SPACING: f32 = 1.3;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+struct AppStyle {
+    graph_cell_size: Vec2,
+    graph_stroke: Stroke,
+    graph_style: GraphStyle,
+    pixels_per_point: f32,
+    font_size: f32,
+}
+
+/// How edges between commits are drawn, mirroring jj's `ui.graph.style`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum GraphStyle {
+    Curved,
+    Square,
+    Ascii,
+    AsciiLarge,
+}
+
+impl Default for AppStyle {
+    fn default() -> Self {
+        AppStyle {
+            graph_cell_size: Vec2::new(16.0, 20.0),
+            graph_stroke: Stroke {
+                width: 1.,
+                color: Color32::from_rgb(104, 148, 187),
+            },
+            graph_style: GraphStyle::Curved,
+            pixels_per_point: 1.2,
+            font_size: 14.0,
+        }
+    }
+}
+
+impl AppStyle {
+    /// The height of every line of the log: a graph cell, or a line of text if the font needs more.
+    fn row_height(&self) -> f32 {
+        self.graph_cell_size.y.max((self.font_size * LINE_SPACING).ceil())
+    }
+
+    /// The size of a graph cell, stretched to the height of a row.
+    fn cell_size(&self) -> Vec2 {
+        Vec2::new(self.graph_cell_size.x, self.row_height())
+    }
+
+    fn text_size(&self) -> TextSize {
+        TextSize {
+            font_size: self.font_size,
+            line_height: self.row_height(),
+        }
+    }
+
+    fn from_settings(settings: &UserSettings) -> Result<AppStyle> {
+        let graph_style = settings
+            .get_string("kahva.graph.style")
+            .optional()
+            .transpose()
+            .unwrap_or_else(|| settings.get_string("ui.graph.style"))?;
+        let graph_style = match graph_style.as_str() {
+            "curved" => GraphStyle::Curved,
+            "square" => GraphStyle::Square,
+            "ascii" => GraphStyle::Ascii,
+            "ascii-large" => GraphStyle::AsciiLarge,
+            other => return Err(eyre!("Invalid graph style '{other}'")),
+        };
+        let compact = settings.get_bool("kahva.graph.compact").optional()?.unwrap_or(false);
+
+        let default = AppStyle::default();
+        let graph_cell_width = match (graph_style, compact) {
+            (_, true) => 10.0,
+            (GraphStyle::AsciiLarge, false) => 24.0,
+            (_, false) => default.graph_cell_size.x,
+        };
+        Ok(AppStyle {
+            graph_cell_size: Vec2::new(graph_cell_width, default.graph_cell_size.y),
+            graph_style,
+            ..default
+        })
+    }
+}
+
+impl eframe::App for App {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.frame(ctx);
+    }
+
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        self.0.save_settings(storage);
+    }
+}
+
+impl UiState {
+    fn update(&mut self, ctx: &egui::Context, content: &RepoView) {
+        // text fields have their own undo
+        if !ctx.wants_keyboard_input() && !self.read_only() {
+            // checked first, the undo shortcut matches with shift held as well
+            let redo = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND | egui::Modifiers::SHIFT, egui::Key::Z);
+            let undo = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Z);
+            if ctx.input_mut(|input| input.consume_shortcut(&redo)) {
+                self.request(Request::Redo);
+            } else if ctx.input_mut(|input| input.consume_shortcut(&undo)) {
+                self.request(Request::Undo);
+            }
+        }
+
+        let theme = self.themes.get(ctx.theme());
+        if self.formatter.theme() != theme {
+            self.style.graph_stroke.color = theme.graph;
+            self.formatter.set_theme(theme.clone());
+            self.formatted_rows.clear();
+        }
+        if self.formatter.text_size() != self.style.text_size() {
+            self.formatter.set_text_size(self.style.text_size());
+            self.formatted_rows.clear();
+        }
+
+        let mut settings_open = self.settings_open;
+        egui::Window::new("Settings")
+            .open(&mut settings_open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let theme = self.themes.get_mut(ctx.theme());
+                if theme_window(ui, &mut self.style, theme) {
+                    self.custom_settings = true;
+                    apply_style(ctx, &self.style, &self.themes);
+                }
+                ui.separator();
+                ui.add_enabled_ui(self.custom_settings, |ui| {
+                    if ui.button("Reset to jj config").clicked() {
+                        let res = self.reset_settings();
+                        self.catch(res);
+                        apply_style(ctx, &self.style, &self.themes);
+                    }
+                });
+            });
+        self.settings_open = settings_open;
+
+        self.draw_errors(ctx);
+
+        egui::Area::new(egui::Id::new("controls"))
+            .anchor(egui::Align2::RIGHT_TOP, [-10., 10.])
+            .show(ctx, |ui| {
+                if self.worker.is_busy() {
+                    ui.spinner().on_hover_text("loading");
+                } else if ui.button("⟳").clicked() {
+                    self.reload();
+                }
+                ui.toggle_value(&mut self.settings_open, "⚙").on_hover_text("settings");
+                let oplog = ui.toggle_value(&mut self.oplog_open, "🕘").on_hover_text("operation log");
+                if oplog.changed() && self.oplog_open {
+                    self.request(Request::LoadOperations);
+                }
+                let clear_filter = self.view_options.filter.as_ref().is_some_and(|filter| {
+                    let button = ui.button(format!("✖ {filter}"));
+                    button.on_hover_text("clear filter").clicked()
+                });
+                if clear_filter {
+                    self.set_filter(None);
+                }
+                if !self.errors.is_empty() {
+                    let errors = format!("⚠ {}", self.errors.len());
+                    ui.toggle_value(&mut self.error_log_open, errors).on_hover_text("error log");
+                }
+                let mut debug = self.formatter.is_debug();
+                if ui.toggle_value(&mut debug, "🐛").on_hover_text("debug labels").changed() {
+                    self.formatter.set_debug(debug);
+                    self.formatted_rows.clear();
+                }
+                let collapse = ui.toggle_value(&mut self.view_options.collapse_stacks, "≡");
+                if collapse.on_hover_text("collapse linear stacks").changed() {
+                    self.view_dirty = true;
+                }
+            });
+
+        self.draw_operation_log(ctx);
+        self.draw_at_operation_banner(ctx);
+        self.draw_stale_banner(ctx, content);
+        self.draw_describe_editor(ctx);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if self.log_lines.is_empty() {
+                self.log_lines = log_lines(content);
+            }
+            let row_height = self.style.row_height();
+
+            let mut scroll_area = egui::ScrollArea::both();
+            if let Some(commit_id) = self.scroll_to.take() {
+                let position = self.log_lines.iter().position(|line| match line {
+                    LogLine::Template { node, line: 0 } => content.nodes[*node].commit_id.as_ref() == Some(&commit_id),
+                    _ => false,
+                });
+                match position {
+                    Some(position) => {
+                        let offset = position as f32 * row_height - ui.available_height() / 2.0;
+                        scroll_area = scroll_area.vertical_scroll_offset(offset.max(0.0));
+                    }
+                    None => {
+                        let error = format!("Commit {} is not shown in the log", commit_id.hex());
+                        self.show_error(ErrorReport::new(error));
+                    }
+                }
+            }
+
+            // every line has to be exactly one row high for `show_rows`
+            let style = ui.style_mut();
+            style.spacing.item_spacing = Vec2::ZERO;
+            style.spacing.interact_size = Vec2::ZERO;
+
+            let lines = std::mem::take(&mut self.log_lines);
+            scroll_area.show_rows(ui, row_height, lines.len(), |ui, range| {
+                for &line in &lines[range] {
+                    match line {
+                        LogLine::Template { node, line } => {
+                            self.draw_line_row(ui, content, node, &content.nodes[node], line);
+                        }
+                        LogLine::Link { node } => {
+                            if let Some(link_row) = &content.nodes[node].row.link_line {
+                                self.draw_line_link(ui, link_row);
+                            }
+                        }
+                        LogLine::Term { node } => {
+                            if let Some(term_row) = &content.nodes[node].row.term_line {
+                                self.draw_line_term(ui, term_row);
+                            }
+                        }
+                        LogLine::LoadMore => self.draw_load_more(ui),
+                    }
+                }
+            });
+            self.log_lines = lines;
+        });
+
+        self.draw_toast(ctx);
+
+        let used_size = ctx.used_size();
+        if !self.initial_sized && !content.nodes.is_empty() && used_size.x > 0. && used_size.x < 5000. {
+            ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(used_size));
+            self.initial_sized = true;
+        }
+    }
+}
+
+impl UiState {
+    fn draw_operation_log(&mut self, ctx: &egui::Context) {
+        if !self.oplog_open {
+            return;
+        }
+
+        let mut selected = None;
+        let mut restore = None;
+        let mut view_at = None;
+        egui::SidePanel::right("operation log").show(ctx, |ui| {
+            ui.heading("Operations");
+            egui::ScrollArea::vertical()
+                .id_salt("operations")
+                .max_height(ui.available_height() / 2.0)
+                .show(ui, |ui| {
+                    for operation in &self.operations {
+                        let is_selected = self.selected_operation.as_ref() == Some(&operation.id);
+                        let short_id = &operation.id.hex()[..12];
+                        let mut title = RichText::new(format!("{short_id} {}", operation.description));
+                        if operation.is_snapshot {
+                            title = title.weak();
+                        }
+                        if ui.selectable_label(is_selected, title).clicked() {
+                            selected = Some(operation.id.clone());
+                        }
+
+                        let mut details = format!("{} {}", operation.time, operation.user);
+                        for (key, value) in &operation.tags {
+                            details.push_str(&format!("\n{key}: {value}"));
+                        }
+                        ui.label(RichText::new(details).weak().small());
+                    }
+                });
+
+            let Some(diff) = &self.operation_diff else {
+                return;
+            };
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.strong(format!("Operation {}", &diff.id.hex()[..12]));
+                if ui.button("View at this operation").clicked() {
+                    view_at = Some(diff.id.clone());
+                }
+                let restore_button = ui.add_enabled(!self.read_only(), egui::Button::new("Restore to this operation"));
+                if restore_button.clicked() {
+                    restore = Some(diff.id.clone());
+                }
+            });
+            egui::ScrollArea::vertical().id_salt("operation diff").show(ui, |ui| {
+                draw_commit_summaries(ui, "Added commits", &diff.added, diff.omitted_added);
+                draw_commit_summaries(ui, "Abandoned commits", &diff.abandoned, diff.omitted_abandoned);
+                if !diff.bookmarks.is_empty() {
+                    ui.strong("Changed bookmarks");
+                    for change in &diff.bookmarks {
+                        ui.label(format!("{}: {} → {}", change.name, change.from, change.to));
+                    }
+                }
+                if diff.added.is_empty() && diff.abandoned.is_empty() && diff.bookmarks.is_empty() {
+                    ui.weak("No commits or bookmarks changed");
+                }
+            });
+        });
+
+        if let Some(op_id) = selected {
+            self.selected_operation = Some(op_id.clone());
+            self.operation_diff = None;
+            self.request(Request::DiffOperation(op_id));
+        }
+        if let Some(op_id) = restore {
+            self.request(Request::RestoreOperation(op_id));
+        }
+        if let Some(op_id) = view_at {
+            self.request(Request::SetOperation(Some(op_id)));
+        }
+    }
+
+    fn draw_at_operation_banner(&mut self, ctx: &egui::Context) {
+        let Some(op_id) = &self.at_operation else {
+            return;
+        };
+
+        let mut back = false;
+        egui::TopBottomPanel::top("at operation").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(format!("Viewing the repo at operation {}, read-only", &op_id.hex()[..12]));
+                ui.checkbox(&mut self.highlight_changed, "highlight commits changed since");
+                back = ui.button("Back to current").clicked();
+            });
+        });
+        if back {
+            self.request(Request::SetOperation(None));
+        }
+    }
+
+    fn draw_stale_banner(&mut self, ctx: &egui::Context, content: &RepoView) {
+        if !content.working_copy_stale || self.read_only() {
+            return;
+        }
+
+        let mut update = false;
+        egui::TopBottomPanel::top("stale working copy").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("The working copy is stale, changes to its files aren't recorded");
+                update = ui.button("Update working copy").clicked();
+            });
+        });
+        if update {
+            self.request(Request::UpdateStale);
+        }
+    }
+}
+
+fn draw_commit_summaries(ui: &mut egui::Ui, heading: &str, commits: &[CommitSummary], omitted: usize) {
+    if commits.is_empty() {
+        return;
+    }
+    ui.strong(heading);
+    for commit in commits {
+        ui.horizontal(|ui| {
+            ui.monospace(&commit.commit_id.hex()[..12]);
+            ui.label(&commit.description);
+        });
+    }
+    if omitted > 0 {
+        ui.weak(format!("and {omitted} more commits"));
+    }
+}
+
+/// The description being edited in the describe window.
+struct DescribeEditor {
+    commit_id: CommitId,
+    /// The text being edited, `None` until the draft from `templates.draft_commit_description` is loaded.
+    text: Option<String>,
+    /// The cleaned up draft as it was loaded, to notice if the description wasn't changed.
+    original: Option<String>,
+}
+
+enum DescribeAction {
+    Save(String),
+    OpenEditor(String),
+    Close,
+}
+
+impl UiState {
+    fn draw_describe_editor(&mut self, ctx: &egui::Context) {
+        let Some(editor) = &mut self.describe_editor else {
+            return;
+        };
+
+        let mut open = true;
+        let mut action = None;
+        egui::Window::new(format!("Describe {}", &editor.commit_id.hex()[..12]))
+            .id(egui::Id::new("describe"))
+            .open(&mut open)
+            .default_width(600.0)
+            .show(ctx, |ui| {
+                let Some(text) = &mut editor.text else {
+                    ui.spinner();
+                    return;
+                };
+                // consumed before the text field would insert a newline
+                let save = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Enter);
+                let save_pressed = ui.input_mut(|input| input.consume_shortcut(&save));
+
+                let text_edit = TextEdit::multiline(text)
+                    .code_editor()
+                    .desired_rows(12)
+                    .desired_width(f32::INFINITY);
+                ui.add(text_edit);
+
+                let description = describe::cleanup_description(text);
+                for hint in self.describe_lints.lint(&description) {
+                    ui.colored_label(ui.visuals().warn_fg_color, hint);
+                }
+                ui.horizontal(|ui| {
+                    let save_button = ui.button("Save").on_hover_text(ctx.format_shortcut(&save));
+                    let open_editor = ui.button("Open in editor");
+                    let open_editor = open_editor.on_hover_text("edit in `ui.editor`, updated once it is closed");
+                    if open_editor.clicked() {
+                        action = Some(DescribeAction::OpenEditor(text.clone()));
+                    }
+                    if ui.button("Cancel").clicked() {
+                        action = Some(DescribeAction::Close);
+                    }
+                    if save_button.clicked() || save_pressed {
+                        action = Some(match editor.original.as_ref() == Some(&description) {
+                            true => DescribeAction::Close,
+                            false => DescribeAction::Save(description),
+                        });
+                    }
+                });
+            });
+
+        let commit_id = editor.commit_id.clone();
+        match action {
+            Some(DescribeAction::Save(description)) => {
+                self.describe_editor = None;
+                let res = self.describe(&commit_id, &description);
+                self.catch(res);
+            }
+            Some(DescribeAction::OpenEditor(text)) => {
+                let res = self.worker.edit_description(&self.settings, commit_id, text);
+                self.catch(res);
+            }
+            Some(DescribeAction::Close) => self.describe_editor = None,
+            None if !open => self.describe_editor = None,
+            None => {}
+        }
+    }
+}
+
+/// How many errors are shown as notifications at once, the others are only in the error log.
+const MAX_NOTIFICATIONS: usize = 5;
+
+const ERROR_COLOR: Color32 = Color32::from_rgb(255, 0, 51);
+
+impl UiState {
+    fn draw_errors(&mut self, ctx: &egui::Context) {
+        let notifications: Vec<_> = self.errors.notifications().collect();
+        let hidden = notifications.len().saturating_sub(MAX_NOTIFICATIONS);
+
+        let mut dismiss = None;
+        egui::Area::new(egui::Id::new("errors"))
+            .anchor(egui::Align2::LEFT_BOTTOM, [10., -10.])
+            .show(ctx, |ui| {
+                ui.set_max_width(500.0);
+                if hidden > 0 {
+                    ui.weak(format!("{hidden} more errors in the error log"));
+                }
+                for (index, report) in &notifications[hidden..] {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label(RichText::new(&report.message).color(ERROR_COLOR));
+                            if ui.small_button("✖").on_hover_text("dismiss").clicked() {
+                                dismiss = Some(*index);
+                            }
+                        });
+                        for cause in &report.causes {
+                            ui.label(RichText::new(cause).monospace().weak());
+                        }
+                    });
+                }
+            });
+        if let Some(index) = dismiss {
+            self.errors.dismiss(index);
+        }
+
+        let mut error_log_open = self.error_log_open;
+        let mut clear = false;
+        egui::Window::new("Errors")
+            .open(&mut error_log_open)
+            .default_width(500.0)
+            .show(ctx, |ui| {
+                clear = ui.button("Clear").clicked();
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for (index, report) in self.errors.reports().enumerate().rev() {
+                        let title = format!("{} {}", report.time.format("%H:%M:%S"), report.message);
+                        egui::CollapsingHeader::new(RichText::new(title).color(ERROR_COLOR))
+                            .id_salt(index)
+                            .show(ui, |ui| {
+                                for cause in &report.causes {
+                                    ui.label(RichText::new(format!("Caused by: {cause}")).monospace());
+                                }
+                                if let Some(backtrace) = &report.backtrace {
+                                    ui.collapsing("Backtrace", |ui| {
+                                        ui.label(RichText::new(backtrace).monospace().small());
+                                    });
+                                }
+                                if ui.small_button("copy").clicked() {
+                                    ui.ctx().copy_text(report.full_text());
+                                }
+                            });
+                    }
+                });
+            });
+        self.error_log_open = error_log_open && !clear;
+        if clear {
+            self.errors.clear();
+        }
+    }
+}
+
+/// How long the toast after an operation is shown.
+const TOAST_DURATION: Duration = Duration::from_secs(6);
+
+struct Toast {
+    message: String,
+    /// Whether the operation was an undo, which can be redone.
+    undone: bool,
+    shown_at: Instant,
+}
+
+impl UiState {
+    fn draw_toast(&mut self, ctx: &egui::Context) {
+        let Some(toast) = &self.toast else {
+            return;
+        };
+        let remaining = TOAST_DURATION.saturating_sub(toast.shown_at.elapsed());
+        if remaining.is_zero() {
+            self.toast = None;
+            return;
+        }
+        ctx.request_repaint_after(remaining);
+
+        let mut request = None;
+        egui::Area::new(egui::Id::new("toast"))
+            .anchor(egui::Align2::RIGHT_BOTTOM, [-10., -10.])
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label(&toast.message);
+                        if ui.button("Undo").clicked() {
+                            request = Some(Request::Undo);
+                        }
+                        if toast.undone && ui.button("Redo").clicked() {
+                            request = Some(Request::Redo);
+                        }
+                    });
+                });
+            });
+        if let Some(request) = request {
+            self.toast = None;
+            self.request(request);
+        }
+    }
+}
+
+/// A line of the log, one row high.
+#[derive(Clone, Copy, Debug)]
+enum LogLine {
+    /// A line of the template output of a node, the first one being the node itself.
+    Template { node: usize, line: usize },
+    /// The edges between a node and the next one.
+    Link { node: usize },
+    /// The lanes ending below a node.
+    Term { node: usize },
+    LoadMore,
+}
+
+fn log_lines(content: &RepoView) -> Vec<LogLine> {
+    let mut lines = Vec::new();
+    for (node, commit_node) in content.nodes.iter().enumerate() {
+        lines.extend((0..commit_node.lines).map(|line| LogLine::Template { node, line }));
+        if commit_node.row.link_line.is_some() {
+            lines.push(LogLine::Link { node });
+        }
+        if commit_node.row.term_line.is_some() {
+            lines.push(LogLine::Term { node });
+        }
+    }
+    if content.has_more {
+        lines.push(LogLine::LoadMore);
+    }
+    lines
+}
+
+#[derive(Debug)]
+enum DropPayload {
+    Bookmark(RefNameBuf),
+}
+
+impl UiState {
+    /// The formatted template output of a node, which is only replayed if it isn't cached yet.
+    fn formatted_row(&mut self, index: usize, node: &CommitNode) -> Arc<[Vec<Section>]> {
+        if self.formatted_rows.len() <= index {
+            self.formatted_rows.resize_with(index + 1, || None);
+        }
+        if let Some(row) = &self.formatted_rows[index] {
+            return row.clone();
+        }
+
+        let formatter = &mut self.formatter;
+        let result = panics::catch(|| node.msg.replay(formatter));
+        // on failure, the row shows what was written until then
+        let row: Arc<[Vec<Section>]> = self.formatter.take().into();
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => self.show_error(ErrorReport::new(format!("Failed to render the log template: {e}"))),
+            Err(report) => self.show_error(report),
+        }
+        self.formatted_rows[index] = Some(row.clone());
+        row
+    }
+
+    /// Draws one line of the template output of a node, the first one next to the node itself.
+    fn draw_line_row(&mut self, ui: &mut egui::Ui, content: &RepoView, index: usize, node: &CommitNode, line: usize) {
+        let id = match (&node.commit_id, &node.elided) {
+            (Some(commit_id), _) => egui::Id::new(commit_id),
+            (None, Some(edge)) => egui::Id::new(edge),
+            (None, None) => egui::Id::new(("log row", index)),
+        };
+
+        let node_line = &node.row.node_line;
+
+        let formatted = self.formatted_row(index, node);
+        let sections = formatted.get(line).map(Vec::as_slice).unwrap_or_default();
+
+        if line > 0 {
+            // continue the graph lanes next to the remaining lines of multi-line templates
+            let node_continues = node
+                .commit_id
+                .as_ref()
+                .and_then(|commit_id| content.parents.get(commit_id))
+                .is_some_and(|parents| !parents.is_empty());
+            ui.horizontal(|ui| {
+                ui.reset_style();
+                self.draw_line_pad(ui, node_line, node_continues);
+                self.draw_sections(ui, node, id.with(line), sections);
+            });
+            return;
+        }
+
+        let row = ui.horizontal(|ui| {
+            ui.reset_style();
+
+            let sense = match node.revealed_by.is_some() || node.expanded_stack {
+                true => egui::Sense::click(),
+                false => egui::Sense::empty(),
+            };
+            let (response, painter) = ui.allocate_painter(
+                self.style.cell_size() * Vec2::new(node_line.len() as f32, 1.0),
+                sense,
+            );
+            for (i, line) in node_line.iter().enumerate() {
+                let rect = rect_subdiv_x(response.rect, node_line.len(), i);
+                if let NodeLine::Blank = line {
+                    continue;
+                }
+
+                let is_head = i == node_line.len() - 1
+                    && node
+                        .commit_id
+                        .as_ref()
+                        .is_some_and(|commit_id| content.heads.contains(commit_id));
+
+                if is_head {
+                    painter.line_segment([rect.center(), rect.center_bottom()], self.style.graph_stroke);
+                } else {
+                    painter.line_segment([rect.center_top(), rect.center_bottom()], self.style.graph_stroke);
+                }
+                if let NodeLine::Node = line {
+                    if node.revealed_by.is_some() || node.expanded_stack {
+                        let fill = ui.visuals().panel_fill;
+                        painter.circle(rect.center() + Vec2::X * 0.25, 3.0, fill, self.style.graph_stroke);
+                    } else {
+                        painter.circle_filled(rect.center() + Vec2::X * 0.25, 3.0, self.style.graph_stroke.color);
+                    }
+                }
+            }
+            if let Some(edge) = &node.revealed_by {
+                let response = response
+                    .on_hover_text("collapse elided revisions")
+                    .on_hover_cursor(egui::CursorIcon::PointingHand);
+                if response.clicked() {
+                    self.collapse_elided(edge);
+                }
+            } else if let Some(commit_id) = node.commit_id.as_ref().filter(|_| node.expanded_stack) {
+                let response = response
+                    .on_hover_text("collapse stack")
+                    .on_hover_cursor(egui::CursorIcon::PointingHand);
+                if response.clicked() {
+                    self.collapse_stack(commit_id);
+                }
+            }
+
+            let msg = |ui: &mut egui::Ui| self.draw_sections(ui, node, id, sections);
+
+            if let Some(commit_id) = &node.commit_id {
+                if DragAndDrop::has_payload_of_type::<DropPayload>(ui.ctx()) {
+                    let frame = egui::Frame::dark_canvas(ui.style())
+                        .outer_margin(Margin::ZERO)
+                        .inner_margin(Margin::ZERO)
+                        .corner_radius(0)
+                        .stroke(Stroke::NONE);
+                    let result = ui.dnd_drop_zone::<DropPayload, _>(frame, msg);
+                    if let Some(result) = result.1 {
+                        self.handle_drop(commit_id, &result);
+                    }
+                } else {
+                    msg(ui);
+                }
+                // ui.dnd_drag_source(egui::Id::new(commit_id), node.commit_id.clone(), msg);
+            } else {
+                msg(ui);
+            }
+        });
+
+        let changed = node
+            .commit_id
+            .as_ref()
+            .is_some_and(|commit_id| content.changed_since.contains(commit_id));
+        if changed && self.highlight_changed {
+            let color = self.formatter.theme().ansi[1].gamma_multiply(0.15);
+            ui.painter().rect_filled(row.response.rect, 0.0, color);
+            row.response.on_hover_text("rewritten or abandoned since this operation");
+        }
+    }
+
+    fn draw_line_pad(&mut self, ui: &mut egui::Ui, node_line: &[NodeLine], node_continues: bool) {
+        let (response, painter) = ui.allocate_painter(
+            self.style.cell_size() * Vec2::new(node_line.len() as f32, 1.0),
+            egui::Sense::empty(),
+        );
+        for (i, line) in node_line.iter().enumerate() {
+            let rect = rect_subdiv_x(response.rect, node_line.len(), i);
+            let continues = match line {
+                NodeLine::Blank => false,
+                NodeLine::Node => node_continues,
+                NodeLine::Parent | NodeLine::Ancestor => true,
+            };
+            if continues {
+                painter.line_segment([rect.center_top(), rect.center_bottom()], self.style.graph_stroke);
+            }
+        }
+    }
+
+    fn draw_sections(&mut self, ui: &mut egui::Ui, node: &CommitNode, id: egui::Id, sections: &[Section]) {
+        let layout = egui::Layout::left_to_right(egui::Align::Center);
+        ui.with_layout(layout, |ui| {
+            ui.style_mut().spacing.item_spacing = Vec2::ZERO;
+            if self.formatter.is_debug() {
+                self.draw_debug_sections(ui, sections);
+                return;
+            }
+
+            for (i, Section { job, label, link, .. }) in sections.iter().enumerate() {
+                // the cached rows are shared, egui only gets a copy of the job
+                let job = job.clone();
+                let action = label.as_deref().and_then(|label| self.spans.action(label));
+                match action {
+                    _ if node.stack.is_some() => {
+                        if clickable_label(ui, job, "expand stack").clicked() {
+                            self.expand_stack(node.stack.as_ref().unwrap());
+                        }
+                    }
+                    _ if node.elided.is_some() => {
+                        if clickable_label(ui, job, "expand elided revisions").clicked() {
+                            self.expand_elided(node.elided.as_ref().unwrap());
+                        }
+                    }
+                    _ if link.is_some() => {
+                        let link = link.as_ref().unwrap();
+                        if clickable_label(ui, job, link).clicked() {
+                            ui.ctx().open_url(egui::OpenUrl::new_tab(link));
+                        }
+                    }
+                    Some(SpanAction::MoveBookmark) if node.commit_id.is_some() && !self.read_only() => {
+                        let bookmark = RefNameBuf::from(job.text.trim().trim_end_matches("*").to_owned());
+                        ui.dnd_drag_source(id.with(i), DropPayload::Bookmark(bookmark), |ui| ui.label(job));
+                    }
+                    Some(SpanAction::Describe) if node.commit_id.is_some() && !self.read_only() => {
+                        if clickable_label(ui, job, "edit description").clicked() {
+                            let commit_id = node.commit_id.clone().unwrap();
+                            self.describe_editor = Some(DescribeEditor {
+                                commit_id: commit_id.clone(),
+                                text: None,
+                                original: None,
+                            });
+                            self.request(Request::LoadDescription(commit_id));
+                        }
+                    }
+                    Some(SpanAction::Copy) => {
+                        let text = job.text.trim().to_owned();
+                        if clickable_label(ui, job, "copy").clicked() {
+                            ui.ctx().copy_text(text);
+                        }
+                    }
+                    Some(SpanAction::FilterAuthor) => {
+                        let author = job.text.trim().to_owned();
+                        if clickable_label(ui, job, "show only commits by this author").clicked() {
+                            self.set_filter(Some(format!("author(exact:{author:?})")));
+                        }
+                    }
+                    Some(SpanAction::JumpToCommit) => {
+                        let revision = job.text.trim().to_owned();
+                        if clickable_label(ui, job, "jump to commit").clicked() {
+                            let res = self.jump_to(&revision);
+                            self.catch(res);
+                        }
+                    }
+                    _ => {
+                        ui.label(job);
+                    }
+                }
+            }
+        });
+    }
+
+    /// Draws every span separately, showing its labels and matching style rules on hover.
+    fn draw_debug_sections(&mut self, ui: &mut egui::Ui, sections: &[Section]) {
+        for section in sections {
+            for (layout_section, labels) in section.job.sections.iter().zip(&section.label_stacks) {
+                let text = &section.job.text[layout_section.byte_range.clone()];
+                let job = LayoutJob::single_section(text.to_owned(), layout_section.format.clone());
+                ui.label(job).on_hover_ui(|ui| self.label_debug_tooltip(ui, labels));
+            }
+        }
+    }
+
+    fn label_debug_tooltip(&self, ui: &mut egui::Ui, labels: &[String]) {
+        ui.label(RichText::new(labels.join(" ")).monospace().strong());
+
+        let rules = self.formatter.matched_rules(labels);
+        if rules.is_empty() {
+            ui.weak("no matching style rules");
+            return;
+        }
+        egui::Grid::new("label_debug_rules").striped(true).show(ui, |ui| {
+            ui.strong("rule");
+            ui.strong("priority");
+            ui.strong("style");
+            ui.end_row();
+            for (rule, style, priority) in rules {
+                ui.monospace(rule.join(" "));
+                ui.monospace(format!("{priority:?}"));
+                ui.monospace(egui_formatter::style_summary(style));
+                ui.end_row();
+            }
+        });
+    }
+
+    fn draw_load_more(&mut self, ui: &mut egui::Ui) {
+        let response = ui
+            .horizontal(|ui| {
+                ui.spacing_mut().item_spacing = Vec2::new(8.0, 0.0);
+                if ui.button("load more").clicked() {
+                    self.load_more();
+                }
+                if ui.button("load all").clicked() {
+                    self.load_all();
+                }
+            })
+            .response;
+
+        // load the next page as soon as the footer is scrolled into view, once the last one arrived
+        let page_pending = self.view_dirty || self.loading_page || self.worker.is_busy();
+        if !page_pending && ui.is_rect_visible(response.rect) {
+            self.load_more();
+        }
+    }
+
+    fn draw_line_term(&mut self, ui: &mut egui::Ui, term_row: &[bool]) {
+        let (response, painter) = ui.allocate_painter(
+            self.style.cell_size() * Vec2::new(term_row.len() as f32, 1.0),
+            egui::Sense::empty(),
+        );
+
+        for (i, _) in term_row.iter().enumerate() {
+            let rect = rect_subdiv_x(response.rect, term_row.len(), i);
+
+            for i in 0..4 {
+                let pos = rect.center_top() + Vec2::DOWN * i as f32 * 3.0;
+                painter.circle_filled(pos + Vec2::X * 0.25, 0.5, self.style.graph_stroke.color);
+            }
+        }
+    }
+
+    fn draw_line_link(&mut self, ui: &mut egui::Ui, link_row: &[LinkLine]) {
+        let (response, painter) = ui.allocate_painter(
+            self.style.cell_size() * Vec2::new(link_row.len() as f32, 1.0),
+            egui::Sense::empty(),
+        );
+
+        let n = link_row.len();
+        for (i, cur) in link_row.iter().enumerate() {
+            let rect = rect_subdiv_x(response.rect, n, i);
+            let first_rect = rect_subdiv_x(response.rect, n, 0);
+            let next_rect = rect_subdiv_x(response.rect, n, i + 1);
+
+            if cur.intersects(LinkLine::HORIZONTAL) {
+                // painter.line_segment([rect.left_center(), rect.right_center()], stroke);
+            }
+            if cur.intersects(LinkLine::VERTICAL) {
+                painter.line_segment([rect.center_top(), rect.center_bottom()], self.style.graph_stroke);
+            }
+            if cur.intersects(LinkLine::RIGHT_FORK) {
+                painter.add(self.edge(next_rect.center_top(), rect.center_bottom()));
+            }
+            if cur.intersects(LinkLine::RIGHT_MERGE) {
+                painter.add(self.edge(rect.center_top(), next_rect.center_bottom()));
+            }
+            if cur.intersects(LinkLine::LEFT_FORK) {
+                painter.add(self.edge(first_rect.center_top(), rect.center_bottom()));
+            }
+            if cur.intersects(LinkLine::LEFT_MERGE) {}
+        }
+    }
+
+    fn edge(&self, from: Pos2, to: Pos2) -> egui::Shape {
+        match self.style.graph_style {
+            GraphStyle::Curved => self.bezier(from, to, Vec2::Y * self.style.row_height() * 0.8).into(),
+            GraphStyle::Square => {
+                let mid_y = (from.y + to.y) / 2.0;
+                let points = vec![from, Pos2::new(from.x, mid_y), Pos2::new(to.x, mid_y), to];
+                egui::Shape::line(points, self.style.graph_stroke)
+            }
+            GraphStyle::Ascii | GraphStyle::AsciiLarge => {
+                egui::Shape::line_segment([from, to], self.style.graph_stroke)
+            }
+        }
+    }
+
+    fn bezier(&self, from: Pos2, to: Pos2, delta: Vec2) -> CubicBezierShape {
+        CubicBezierShape {
+            points: [from, from + delta, to - delta, to],
+            closed: false,
+            fill: Color32::TRANSPARENT,
+            stroke: PathStroke {
+                width: self.style.graph_stroke.width,
+                color: ColorMode::Solid(self.style.graph_stroke.color),
+                kind: StrokeKind::Middle,
+            },
+        }
+    }
+
+    fn catch<T, E: Into<ErrorReport>>(&mut self, res: Result<T, E>) -> Option<T> {
+        match res {
+            Ok(value) => Some(value),
+            Err(error) => {
+                self.show_error(error.into());
+                None
+            }
+        }
+    }
+
+    fn show_error(&mut self, report: ErrorReport) {
+        eprintln!("{}", report.full_text());
+        self.errors.push(report);
+    }
+
+    fn handle_drop(&mut self, commit: &CommitId, payload: &DropPayload) {
+        match payload {
+            DropPayload::Bookmark(bookmark) => {
+                self.request(Request::MoveBookmark {
+                    bookmark: bookmark.clone(),
+                    commit_id: commit.clone(),
+                });
+            }
+        }
+    }
+}
+
+fn clickable_label(ui: &mut egui::Ui, job: LayoutJob, hover_text: &str) -> egui::Response {
+    egui::Label::new(job)
+        .sense(egui::Sense::click())
+        .ui(ui)
+        .on_hover_text(hover_text)
+        .on_hover_cursor(egui::CursorIcon::PointingHand)
+}
+
+fn rect_subdiv_x(rect: Rect, n_x: usize, i: usize) -> Rect {
+    let w = rect.width() / n_x as f32;
+    Rect::from_min_size(
+        Pos2::new(rect.min.x + w * i as f32, rect.min.y),
+        Vec2::new(w, rect.height()),
+    )
+}
+
+/// Returns whether any setting was changed.
+fn theme_window(ui: &mut egui::Ui, style: &mut AppStyle, theme: &mut ColorTheme) -> bool {
+    let mut changed = false;
+    egui::Grid::new("settings").show(ui, |ui| {
+        const POSITIVE: RangeInclusive<f32> = 1.0..=f32::MAX;
+        ui.label("Size (x)");
+        ui.horizontal(|ui| {
+            changed |= ui
+                .add(egui::DragValue::new(&mut style.graph_cell_size.x).range(POSITIVE))
+                .changed();
+            changed |= ui
+                .add(egui::DragValue::new(&mut style.graph_cell_size.y).range(POSITIVE))
+                .changed();
+        });
+        ui.end_row();
+        ui.label("Stroke Width");
+        changed |= ui
+            .add(
+                egui::DragValue::new(&mut style.graph_stroke.width)
+                    .range(0.1..=5.0)
+                    .speed(0.01),
+            )
+            .changed();
+        ui.end_row();
+        ui.label("Stroke Color");
+        changed |= ui.color_edit_button_srgba(&mut theme.graph).changed();
+        ui.end_row();
+
+        ui.label("Background Color");
+        changed |= ui.color_edit_button_srgba(&mut theme.background).changed();
+        ui.end_row();
+        ui.label("Text Color");
+        changed |= ui.color_edit_button_srgba(&mut theme.foreground).changed();
+        ui.end_row();
+
+        ui.label("PPP");
+        let ppp = ui.add(
+            egui::DragValue::new(&mut style.pixels_per_point)
+                .range(0.1..=5.0)
+                .speed(0.01),
+        );
+        if ppp.changed() {
+            ui.ctx().stop_dragging();
+            changed = true;
+        }
+        ui.end_row();
+
+        ui.label("Font Size");
+        changed |= ui
+            .add(egui::DragValue::new(&mut style.font_size).range(6.0..=40.0).speed(0.1))
+            .changed();
+        ui.end_row();
+    });
+
+    ui.collapsing("Palette", |ui| {
+        egui::Grid::new("palette").num_columns(4).show(ui, |ui| {
+            for (i, (name, color)) in ANSI_NAMES.iter().zip(&mut theme.ansi).enumerate() {
+                ui.label(*name);
+                changed |= ui.color_edit_button_srgba(color).changed();
+                if i % 2 == 1 {
+                    ui.end_row();
+                }
+            }
+        });
+    });
+
+    changed
+}
@@ -0,0 +1,62 @@
+use color_eyre::Result;
+use color_eyre::eyre::eyre;
+use jj_lib::config::{ConfigGetResultExt, StackedConfig};
+use regex::Regex;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Patterns which are turned into hyperlinks when they appear in the log,
+/// e.g. `#(\d+)` linking to an issue tracker.
+#[derive(Debug, Default)]
+pub struct AutoLinks {
+    links: Vec<(Regex, String)>,
+}
+
+impl AutoLinks {
+    /// Loads the `kahva.links` table, mapping regexes to URL templates.
+    ///
+    /// The URL template can refer to capture groups of the pattern, e.g. `$1` or `${name}`.
+    pub fn from_config(config: &StackedConfig) -> Result<AutoLinks> {
+        let configured = config
+            .get::<HashMap<String, String>>("kahva.links")
+            .optional()?
+            .unwrap_or_default();
+
+        let mut links = Vec::with_capacity(configured.len());
+        for (pattern, url) in configured {
+            let regex =
+                Regex::new(&pattern).map_err(|e| eyre!("Invalid pattern '{pattern}' in kahva.links: {e}"))?;
+            links.push((regex, url));
+        }
+        // config tables are unordered, keep the matching deterministic
+        links.sort_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
+
+        Ok(AutoLinks { links })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+
+    /// The non-overlapping matches in `text` with their expanded URLs, in order.
+    pub fn find(&self, text: &str) -> Vec<(Range<usize>, String)> {
+        let mut matches: Vec<(Range<usize>, String)> = Vec::new();
+        for (regex, template) in &self.links {
+            for captures in regex.captures_iter(text) {
+                let range = captures.get(0).unwrap().range();
+                if range.is_empty() || matches.iter().any(|(other, _)| overlaps(other, &range)) {
+                    continue;
+                }
+                let mut url = String::new();
+                captures.expand(template, &mut url);
+                matches.push((range, url));
+            }
+        }
+        matches.sort_by_key(|(range, _)| range.start);
+        matches
+    }
+}
+
+fn overlaps(a: &Range<usize>, b: &Range<usize>) -> bool {
+    a.start < b.end && b.start < a.end
+}
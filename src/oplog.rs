@@ -0,0 +1,145 @@
+use crate::jj::Repo;
+use chrono::TimeZone as _;
+use color_eyre::Result;
+use jj_lib::backend::{CommitId, Timestamp};
+use jj_lib::object_id::ObjectId;
+use jj_lib::op_store::{OperationId, RefTarget};
+use jj_lib::refs::diff_named_ref_targets;
+use jj_lib::repo::{ReadonlyRepo, Repo as _};
+use jj_lib::revset::RevsetExpression;
+use std::sync::Arc;
+
+/// Number of operations shown in the operation log.
+pub const OPERATION_LIMIT: usize = 200;
+/// Number of added or abandoned commits listed per operation.
+const COMMIT_LIMIT: usize = 100;
+
+pub struct OperationEntry {
+    pub id: OperationId,
+    pub description: String,
+    /// Tags like `args` of jj commands, or the `bookmark` moved by kahva.
+    pub tags: Vec<(String, String)>,
+    pub time: String,
+    /// `user@host`
+    pub user: String,
+    pub is_snapshot: bool,
+}
+
+pub fn load_operations(repo: &Repo) -> Result<Vec<OperationEntry>> {
+    let entries = repo
+        .operation_log(OPERATION_LIMIT)?
+        .into_iter()
+        .map(|operation| {
+            let metadata = operation.metadata();
+            let mut tags: Vec<_> = metadata.tags.clone().into_iter().collect();
+            tags.sort();
+            OperationEntry {
+                id: operation.id().clone(),
+                description: metadata.description.clone(),
+                tags,
+                time: format_timestamp(&metadata.start_time),
+                user: format!("{}@{}", metadata.username, metadata.hostname),
+                is_snapshot: metadata.is_snapshot,
+            }
+        })
+        .collect();
+    Ok(entries)
+}
+
+/// What an operation changed compared to its parent, like `jj op diff`.
+pub struct OperationDiff {
+    pub id: OperationId,
+    pub added: Vec<CommitSummary>,
+    /// Added commits which weren't listed because of the limit.
+    pub omitted_added: usize,
+    pub abandoned: Vec<CommitSummary>,
+    /// Abandoned commits which weren't listed because of the limit.
+    pub omitted_abandoned: usize,
+    pub bookmarks: Vec<BookmarkChange>,
+}
+
+pub struct CommitSummary {
+    pub commit_id: CommitId,
+    pub description: String,
+}
+
+pub struct BookmarkChange {
+    pub name: String,
+    pub from: String,
+    pub to: String,
+}
+
+pub fn diff_operation(repo: &Repo, op_id: &OperationId) -> Result<OperationDiff> {
+    let operation = repo.operation(op_id)?;
+    let to_repo = repo.load_at_operation(op_id)?;
+    // merge operations are compared to their first parent
+    let from_repo = match operation.parent_ids().first() {
+        Some(parent_id) => Some(repo.load_at_operation(parent_id)?),
+        None => None,
+    };
+    let from_heads: Vec<CommitId> = match &from_repo {
+        Some(from_repo) => from_repo.view().heads().iter().cloned().collect(),
+        None => Vec::new(),
+    };
+    let to_heads: Vec<CommitId> = to_repo.view().heads().iter().cloned().collect();
+
+    // the index of the newer repo contains the commits of both
+    // only the listed commits are loaded, the others are just counted
+    let summarize = |from: &[CommitId], to: &[CommitId]| -> Result<(Vec<CommitSummary>, usize)> {
+        let expression = RevsetExpression::commits(from.to_vec()).range(&RevsetExpression::commits(to.to_vec()));
+        let revset = expression.evaluate(to_repo.as_ref())?;
+        let mut commit_ids = revset.iter();
+        let mut commits = Vec::new();
+        for commit_id in commit_ids.by_ref().take(COMMIT_LIMIT) {
+            let commit = to_repo.store().get_commit(&commit_id?)?;
+            commits.push(CommitSummary {
+                commit_id: commit.id().clone(),
+                description: commit.description().lines().next().unwrap_or_default().to_owned(),
+            });
+        }
+        Ok((commits, commit_ids.count()))
+    };
+    let (added, omitted_added) = summarize(&from_heads, &to_heads)?;
+    let (abandoned, omitted_abandoned) = summarize(&to_heads, &from_heads)?;
+
+    let bookmarks = match &from_repo {
+        Some(from_repo) => bookmark_changes(from_repo, &to_repo),
+        None => Vec::new(),
+    };
+
+    Ok(OperationDiff {
+        id: operation.id().clone(),
+        added,
+        omitted_added,
+        abandoned,
+        omitted_abandoned,
+        bookmarks,
+    })
+}
+
+fn bookmark_changes(from_repo: &Arc<ReadonlyRepo>, to_repo: &Arc<ReadonlyRepo>) -> Vec<BookmarkChange> {
+    diff_named_ref_targets(from_repo.view().local_bookmarks(), to_repo.view().local_bookmarks())
+        .map(|(name, (from, to))| BookmarkChange {
+            name: name.as_str().to_owned(),
+            from: format_target(from),
+            to: format_target(to),
+        })
+        .collect()
+}
+
+fn format_target(target: &RefTarget) -> String {
+    if target.is_absent() {
+        "(absent)".to_owned()
+    } else if let Some(commit_id) = target.as_normal() {
+        commit_id.hex()[..12].to_owned()
+    } else {
+        "(conflicted)".to_owned()
+    }
+}
+
+fn format_timestamp(timestamp: &Timestamp) -> String {
+    match chrono::Local.timestamp_millis_opt(timestamp.timestamp.0).single() {
+        Some(time) => time.format("%Y-%m-%d %H:%M:%S").to_string(),
+        None => "(invalid time)".to_owned(),
+    }
+}
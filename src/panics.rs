@@ -0,0 +1,47 @@
+use crate::errors::ErrorReport;
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::cell::RefCell;
+use std::panic::{self, AssertUnwindSafe};
+
+thread_local! {
+    /// The last panic on this thread, recorded by the hook for [`catch`].
+    static LAST_PANIC: RefCell<Option<ErrorReport>> = const { RefCell::new(None) };
+}
+
+/// Records panics with their backtrace so [`catch`] can report them,
+/// in addition to the previously installed hook printing them.
+pub fn install_hook() {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let mut report = ErrorReport::new(format!("kahva panicked: {}", payload_message(info.payload())));
+        if let Some(location) = info.location() {
+            report.causes.push(format!("at {location}"));
+        }
+        report.backtrace = Some(Backtrace::force_capture().to_string());
+        LAST_PANIC.with(|last| *last.borrow_mut() = Some(report));
+
+        previous(info);
+    }));
+}
+
+/// Runs `f`, turning a panic into an error report instead of unwinding further.
+///
+/// Whatever `f` was modifying may be left halfway, the caller has to restore a consistent state.
+pub fn catch<T>(f: impl FnOnce() -> T) -> Result<T, ErrorReport> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+        LAST_PANIC
+            .with(|last| last.borrow_mut().take())
+            .unwrap_or_else(|| ErrorReport::new(format!("kahva panicked: {}", payload_message(payload.as_ref()))))
+    })
+}
+
+fn payload_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic payload"
+    }
+}
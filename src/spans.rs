@@ -0,0 +1,92 @@
+use color_eyre::Result;
+use color_eyre::eyre::eyre;
+use jj_lib::config::{ConfigGetResultExt, StackedConfig};
+use std::collections::HashMap;
+
+/// What happens when interacting with a span of a log row.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpanAction {
+    /// Drag the bookmark onto another commit to move it there.
+    MoveBookmark,
+    /// Edit the description in place.
+    Describe,
+    /// Copy the text to the clipboard.
+    Copy,
+    /// Only show commits by this author.
+    FilterAuthor,
+    /// Scroll to the commit the text refers to.
+    JumpToCommit,
+}
+
+impl SpanAction {
+    fn from_name(name: &str) -> Option<Option<SpanAction>> {
+        let action = match name {
+            "none" => None,
+            "move-bookmark" => Some(SpanAction::MoveBookmark),
+            "describe" => Some(SpanAction::Describe),
+            "copy" => Some(SpanAction::Copy),
+            "filter-author" => Some(SpanAction::FilterAuthor),
+            "jump-to-commit" => Some(SpanAction::JumpToCommit),
+            _ => return None,
+        };
+        Some(action)
+    }
+}
+
+const DEFAULT_SPANS: &[(&str, SpanAction)] = &[
+    ("bookmarks name", SpanAction::MoveBookmark),
+    ("description", SpanAction::Describe),
+    ("change_id", SpanAction::Copy),
+    ("commit_id", SpanAction::JumpToCommit),
+    ("author email", SpanAction::FilterAuthor),
+    ("tags name", SpanAction::Copy),
+    ("remote_bookmarks name", SpanAction::Copy),
+];
+
+/// Label paths which are rendered as interactive spans, e.g. `bookmarks name`.
+///
+/// A span matches if the label stack ends with its labels.
+pub struct InteractiveSpans {
+    spans: Vec<(Vec<String>, SpanAction)>,
+}
+
+impl InteractiveSpans {
+    /// Loads the default spans, overridden by the `kahva.interactive-labels` table.
+    pub fn from_config(config: &StackedConfig) -> Result<InteractiveSpans> {
+        let mut spans: HashMap<String, Option<SpanAction>> = DEFAULT_SPANS
+            .iter()
+            .map(|&(path, action)| (path.to_owned(), Some(action)))
+            .collect();
+
+        let configured = config
+            .get::<HashMap<String, String>>("kahva.interactive-labels")
+            .optional()?
+            .unwrap_or_default();
+        for (path, name) in configured {
+            let action = SpanAction::from_name(&name)
+                .ok_or_else(|| eyre!("Invalid action '{name}' for kahva.interactive-labels.\"{path}\""))?;
+            spans.insert(path, action);
+        }
+
+        let mut spans: Vec<_> = spans
+            .into_iter()
+            .filter_map(|(path, action)| Some((path.split_whitespace().map(str::to_owned).collect(), action?)))
+            .collect();
+        // prefer the most specific match
+        spans.sort_by_key(|(path, _): &(Vec<String>, _)| std::cmp::Reverse(path.len()));
+
+        Ok(InteractiveSpans { spans })
+    }
+
+    pub fn label_paths(&self) -> Vec<Vec<String>> {
+        self.spans.iter().map(|(path, _)| path.clone()).collect()
+    }
+
+    /// The action for a [`Section`](crate::egui_formatter::Section) label.
+    pub fn action(&self, label: &str) -> Option<SpanAction> {
+        self.spans
+            .iter()
+            .find(|(path, _)| path.join(" ") == label)
+            .map(|&(_, action)| action)
+    }
+}
@@ -0,0 +1,182 @@
+use color_eyre::Result;
+use color_eyre::eyre::eyre;
+use egui::Color32;
+use jj_cli::formatter::Color;
+use jj_lib::config::{ConfigGetResultExt, StackedConfig};
+
+/// Names of the 16 ANSI colors as used in jj's `[colors]` config, in palette order.
+pub const ANSI_NAMES: [&str; 16] = [
+    "black",
+    "red",
+    "green",
+    "yellow",
+    "blue",
+    "magenta",
+    "cyan",
+    "white",
+    "bright black",
+    "bright red",
+    "bright green",
+    "bright yellow",
+    "bright blue",
+    "bright magenta",
+    "bright cyan",
+    "bright white",
+];
+
+/// The colors jj's terminal colors are mapped to.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ColorTheme {
+    pub foreground: Color32,
+    pub background: Color32,
+    pub graph: Color32,
+    /// The 16 ANSI colors, in the order of [`ANSI_NAMES`].
+    pub ansi: [Color32; 16],
+}
+
+impl ColorTheme {
+    pub fn dark() -> ColorTheme {
+        ColorTheme {
+            foreground: Color32::WHITE,
+            background: Color32::from_rgb(28, 30, 34),
+            graph: Color32::from_rgb(104, 148, 187),
+            ansi: [
+                Color32::from_rgb(0, 0, 0),
+                Color32::from_rgb(187, 0, 0),
+                Color32::from_rgb(0, 187, 0),
+                Color32::from_rgb(187, 187, 0),
+                Color32::from_rgb(0, 0, 187),
+                Color32::from_rgb(187, 0, 187),
+                Color32::from_rgb(0, 187, 187),
+                Color32::from_rgb(187, 187, 187),
+                Color32::from_rgb(85, 85, 85),
+                Color32::from_rgb(255, 85, 85),
+                Color32::from_rgb(85, 255, 85),
+                Color32::from_rgb(255, 255, 85),
+                Color32::from_rgb(85, 85, 255),
+                Color32::from_rgb(255, 85, 255),
+                Color32::from_rgb(85, 255, 255),
+                Color32::from_rgb(255, 255, 255),
+            ],
+        }
+    }
+
+    pub fn light() -> ColorTheme {
+        ColorTheme {
+            foreground: Color32::from_rgb(30, 30, 30),
+            background: Color32::from_rgb(248, 248, 248),
+            graph: Color32::from_rgb(60, 110, 160),
+            ansi: [
+                Color32::from_rgb(0, 0, 0),
+                Color32::from_rgb(205, 49, 49),
+                Color32::from_rgb(0, 135, 0),
+                Color32::from_rgb(148, 132, 0),
+                Color32::from_rgb(4, 81, 165),
+                Color32::from_rgb(188, 5, 188),
+                Color32::from_rgb(5, 132, 168),
+                Color32::from_rgb(110, 110, 110),
+                Color32::from_rgb(102, 102, 102),
+                Color32::from_rgb(170, 20, 20),
+                Color32::from_rgb(0, 110, 0),
+                Color32::from_rgb(120, 110, 0),
+                Color32::from_rgb(0, 60, 140),
+                Color32::from_rgb(150, 0, 150),
+                Color32::from_rgb(0, 110, 140),
+                Color32::from_rgb(165, 165, 165),
+            ],
+        }
+    }
+
+    /// Overrides colors from the `kahva.theme.<name>` table, e.g. `red = "#ff5555"`.
+    fn with_config(mut self, config: &StackedConfig, name: &str) -> Result<ColorTheme> {
+        let named = [
+            ("foreground", &mut self.foreground),
+            ("background", &mut self.background),
+            ("graph", &mut self.graph),
+        ];
+        let ansi = ANSI_NAMES.iter().copied().zip(self.ansi.iter_mut());
+        for (key, color) in named.into_iter().chain(ansi) {
+            let Some(value) = config.get::<String>(["kahva", "theme", name, key]).optional()? else {
+                continue;
+            };
+            *color = Color32::from_hex(&value)
+                .map_err(|e| eyre!("Invalid color '{value}' for kahva.theme.{name}.{key}: {e:?}"))?;
+        }
+        Ok(self)
+    }
+
+    pub fn color(&self, color: Color) -> Color32 {
+        match color {
+            Color::Reset => self.foreground,
+            Color::Black => self.ansi[0],
+            Color::DarkRed => self.ansi[1],
+            Color::DarkGreen => self.ansi[2],
+            Color::DarkYellow => self.ansi[3],
+            Color::DarkBlue => self.ansi[4],
+            Color::DarkMagenta => self.ansi[5],
+            Color::DarkCyan => self.ansi[6],
+            Color::Grey => self.ansi[7],
+            Color::DarkGrey => self.ansi[8],
+            Color::Red => self.ansi[9],
+            Color::Green => self.ansi[10],
+            Color::Yellow => self.ansi[11],
+            Color::Blue => self.ansi[12],
+            Color::Magenta => self.ansi[13],
+            Color::Cyan => self.ansi[14],
+            Color::White => self.ansi[15],
+            Color::Rgb { r, g, b } => Color32::from_rgb(r, g, b),
+            Color::AnsiValue(value) => self.ansi_value(value),
+        }
+    }
+
+    /// Resolves a color of the xterm 256-color palette.
+    fn ansi_value(&self, value: u8) -> Color32 {
+        match value {
+            0..=15 => self.ansi[value as usize],
+            16..=231 => {
+                // 6x6x6 color cube
+                let level = |i: u8| match i {
+                    0 => 0,
+                    i => 55 + i * 40,
+                };
+                let i = value - 16;
+                Color32::from_rgb(level(i / 36), level(i / 6 % 6), level(i % 6))
+            }
+            232..=255 => {
+                // grayscale ramp
+                let gray = 8 + (value - 232) * 10;
+                Color32::from_gray(gray)
+            }
+        }
+    }
+}
+
+/// The dark and light [`ColorTheme`], selected by the current egui theme.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Themes {
+    pub dark: ColorTheme,
+    pub light: ColorTheme,
+}
+
+impl Themes {
+    pub fn from_config(config: &StackedConfig) -> Result<Themes> {
+        Ok(Themes {
+            dark: ColorTheme::dark().with_config(config, "dark")?,
+            light: ColorTheme::light().with_config(config, "light")?,
+        })
+    }
+
+    pub fn get(&self, theme: egui::Theme) -> &ColorTheme {
+        match theme {
+            egui::Theme::Dark => &self.dark,
+            egui::Theme::Light => &self.light,
+        }
+    }
+
+    pub fn get_mut(&mut self, theme: egui::Theme) -> &mut ColorTheme {
+        match theme {
+            egui::Theme::Dark => &mut self.dark,
+            egui::Theme::Light => &mut self.light,
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use color_eyre::Result;
+use jj_lib::object_id::ObjectId;
+use jj_lib::op_store::OperationId;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+/// How long the repo has to be quiet before reloading, so that one jj command only causes one reload.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Watches the operation heads of a repo to notice operations done by other jj processes.
+pub struct RepoWatcher {
+    _watcher: RecommendedWatcher,
+    changes: Receiver<()>,
+    last_change: Option<Instant>,
+    op_heads: PathBuf,
+    /// The operation done by kahva itself, which is reloaded already.
+    own_operation: Option<OperationId>,
+}
+
+impl RepoWatcher {
+    pub fn new(repo_path: &Path, ctx: egui::Context) -> Result<RepoWatcher> {
+        let (tx, changes) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            if event.is_ok_and(|event| !event.kind.is_access()) {
+                let _ = tx.send(());
+                ctx.request_repaint();
+            }
+        })?;
+        let op_heads = repo_path.join("op_heads");
+        watcher.watch(&op_heads, RecursiveMode::Recursive)?;
+
+        Ok(RepoWatcher {
+            _watcher: watcher,
+            changes,
+            last_change: None,
+            op_heads,
+            own_operation: None,
+        })
+    }
+
+    /// Doesn't report the change to this operation head, because kahva created it.
+    pub fn ignore_operation(&mut self, operation: OperationId) {
+        self.own_operation = Some(operation);
+    }
+
+    /// Whether the repo changed on disk and has been quiet long enough to be reloaded.
+    pub fn poll(&mut self, ctx: &egui::Context) -> bool {
+        if self.changes.try_iter().count() > 0 {
+            self.last_change = Some(Instant::now());
+        }
+        match self.last_change {
+            Some(at) if at.elapsed() >= DEBOUNCE => {
+                self.last_change = None;
+                !self.is_own_operation()
+            }
+            Some(at) => {
+                ctx.request_repaint_after(DEBOUNCE - at.elapsed());
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Whether the only operation head is the one kahva created, unreadable heads count as changes.
+    fn is_own_operation(&self) -> bool {
+        let Some(own_operation) = &self.own_operation else {
+            return false;
+        };
+        let Ok(entries) = std::fs::read_dir(self.op_heads.join("heads")) else {
+            return false;
+        };
+        let heads: Vec<_> = entries.filter_map(|entry| entry.ok()).map(|entry| entry.file_name()).collect();
+        matches!(heads.as_slice(), [head] if head == own_operation.hex().as_str())
+    }
+}
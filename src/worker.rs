@@ -0,0 +1,428 @@
+use crate::Args;
+use crate::backend::{LogGraph, RepoView, ViewOptions};
+use crate::errors::ErrorReport;
+use crate::jj::{LoadOptions, Repo};
+use crate::oplog::{self, OperationDiff, OperationEntry};
+use crate::panics;
+use color_eyre::Result;
+use color_eyre::eyre::{ContextCompat, bail, eyre};
+use jj_cli::text_editor::TextEditor;
+use jj_lib::backend::CommitId;
+use jj_lib::object_id::ObjectId;
+use jj_lib::op_store::OperationId;
+use jj_lib::ref_name::RefNameBuf;
+use jj_lib::settings::UserSettings;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+pub enum Request {
+    /// Loads the view, reloading the repo from disk first if `reload_repo` is set.
+    Reload {
+        reload_repo: bool,
+        options: ViewOptions,
+        generation: u64,
+    },
+    Describe { commit_id: CommitId, description: String },
+    /// Loads the text to edit the description of a commit in.
+    LoadDescription(CommitId),
+    MoveBookmark { bookmark: RefNameBuf, commit_id: CommitId },
+    /// Resolves a revision to jump to.
+    Resolve(String),
+    /// Reverts the last operation, or the one before the last undo.
+    Undo,
+    /// Reapplies the last undone operation.
+    Redo,
+    LoadOperations,
+    DiffOperation(OperationId),
+    /// Restores the repo to the state after the operation, like `jj op restore`.
+    RestoreOperation(OperationId),
+    /// Browses the repo at a past operation, or at the head operation again for `None`.
+    SetOperation(Option<OperationId>),
+    /// Updates the stale working copy, like `jj workspace update-stale`.
+    UpdateStale,
+}
+
+/// The answer to a [`Request`], every request gets exactly one.
+pub enum Response {
+    /// The loaded view, with the new settings if the config changed when the repo was reloaded.
+    View {
+        view: RepoView,
+        /// Whether the repo was reloaded from disk, so there may be new operations.
+        reloaded: bool,
+        settings: Option<UserSettings>,
+    },
+    /// More commits of the loaded view, which were walked and rendered after the ones of the last page.
+    Page(RepoView),
+    /// A reload was abandoned because a newer one was requested.
+    Cancelled,
+    /// The repo was modified and needs to be reloaded.
+    Changed {
+        message: String,
+        /// Whether this was an undo, which can be redone.
+        undone: bool,
+        /// The operation the modification created.
+        operation: OperationId,
+    },
+    Resolved(CommitId),
+    /// The text to edit the description of a commit in.
+    Description { commit_id: CommitId, text: String },
+    /// The repo was loaded at a different operation and needs to be reloaded.
+    AtOperation(Option<OperationId>),
+    Operations(Vec<OperationEntry>),
+    OperationDiff(OperationDiff),
+    Error(ErrorReport),
+}
+
+/// Handle to the thread which owns the [`Repo`] and does all loading and modifications,
+/// so that the UI never waits on jj.
+pub struct Worker {
+    requests: Sender<Request>,
+    responses: Receiver<Response>,
+    /// For answers which don't come from the worker thread.
+    response_tx: Sender<Response>,
+    /// The generation of the most recent reload, older ones are abandoned.
+    latest_reload: Arc<AtomicU64>,
+    /// Number of requests which haven't been answered yet.
+    pending: usize,
+    repo_path: PathBuf,
+}
+
+impl Worker {
+    /// Starts the worker thread, returning once the repo is loaded.
+    pub fn spawn(args: Args) -> Result<(Worker, UserSettings, Option<OperationId>)> {
+        let (request_tx, request_rx) = mpsc::channel();
+        let (response_tx, response_rx) = mpsc::channel();
+        let (loaded_tx, loaded_rx) = mpsc::channel();
+        let latest_reload = Arc::new(AtomicU64::new(0));
+
+        let latest = Arc::clone(&latest_reload);
+        let worker_response_tx = response_tx.clone();
+        std::thread::Builder::new().name("kahva-worker".to_owned()).spawn(move || {
+            // `Repo` isn't `Send`, so it has to be loaded on this thread
+            let options = LoadOptions {
+                ignore_working_copy: args.ignore_working_copy,
+                at_operation: args.at_operation.clone(),
+                ignore_immutable: args.ignore_immutable,
+            };
+            let repo = panics::catch(|| Repo::detect(&args.repository, options))
+                .map_err(ErrorReport::into_report)
+                .and_then(|repo| repo)
+                .and_then(|repo| repo.with_context(|| format!("No repo was found at {}", args.repository.display())));
+            let repo = match repo {
+                Ok(repo) => {
+                    let loaded = (
+                        repo.settings().clone(),
+                        repo.repo_path().to_owned(),
+                        repo.at_operation().cloned(),
+                    );
+                    let _ = loaded_tx.send(Ok(loaded));
+                    repo
+                }
+                Err(e) => {
+                    let _ = loaded_tx.send(Err(e));
+                    return;
+                }
+            };
+            run(repo, &args, request_rx, worker_response_tx, &latest);
+        })?;
+
+        let (settings, repo_path, at_operation) = loaded_rx
+            .recv()
+            .map_err(|_| eyre!("The worker thread stopped while loading the repo"))??;
+        let worker = Worker {
+            requests: request_tx,
+            responses: response_rx,
+            response_tx,
+            latest_reload,
+            pending: 0,
+            repo_path,
+        };
+        Ok((worker, settings, at_operation))
+    }
+
+    pub fn send(&mut self, request: Request) -> Result<()> {
+        self.requests
+            .send(request)
+            .map_err(|_| eyre!("The worker thread has stopped"))?;
+        self.pending += 1;
+        Ok(())
+    }
+
+    /// Starts loading the view, abandoning reloads which are still queued or in progress.
+    pub fn reload(&mut self, reload_repo: bool, options: ViewOptions) -> Result<()> {
+        let generation = self.latest_reload.fetch_add(1, Ordering::Relaxed) + 1;
+        self.send(Request::Reload {
+            reload_repo,
+            options,
+            generation,
+        })
+    }
+
+    /// Edits the text of a description in `ui.editor`, answered with a [`Response::Description`]
+    /// once it is closed. The editor runs on its own thread, so the worker stays responsive meanwhile.
+    pub fn edit_description(&mut self, settings: &UserSettings, commit_id: CommitId, text: String) -> Result<()> {
+        let editor = TextEditor::from_settings(settings)?;
+        let responses = self.response_tx.clone();
+        std::thread::Builder::new().name("kahva-editor".to_owned()).spawn(move || {
+            let response = match editor.edit_str(&text, Some(".jjdescription")) {
+                Ok(text) => Response::Description { commit_id, text },
+                Err(err) => Response::Error(color_eyre::Report::new(err).into()),
+            };
+            let _ = responses.send(response);
+        })?;
+        self.pending += 1;
+        Ok(())
+    }
+
+    /// The `.jj/repo` directory of the loaded repo.
+    pub fn repo_path(&self) -> &Path {
+        &self.repo_path
+    }
+
+    /// Whether there are requests which haven't been answered yet.
+    pub fn is_busy(&self) -> bool {
+        self.pending > 0
+    }
+
+    /// The responses which arrived since the last call.
+    pub fn poll(&mut self) -> Vec<Response> {
+        let responses: Vec<_> = self.responses.try_iter().collect();
+        self.pending -= responses.len();
+        responses
+    }
+}
+
+fn run(
+    mut repo: Repo,
+    args: &Args,
+    requests: Receiver<Request>,
+    responses: Sender<Response>,
+    latest_reload: &AtomicU64,
+) {
+    // set when an abandoned reload should have reloaded the repo, or a request panicked, so the next one does
+    let mut repo_stale = false;
+    let mut undo = UndoState::default();
+    // the walk through the log which loaded the current view, continued by loading more pages
+    let mut log_graph = None;
+
+    for request in requests {
+        let read_only = matches!(
+            request,
+            Request::Reload { .. }
+                | Request::LoadDescription(_)
+                | Request::Resolve(_)
+                | Request::LoadOperations
+                | Request::DiffOperation(_)
+        );
+        let response = panics::catch(|| match request {
+            Request::Reload {
+                reload_repo,
+                options,
+                generation,
+            } => {
+                let is_stale = || latest_reload.load(Ordering::Relaxed) != generation;
+                repo_stale |= reload_repo;
+                if is_stale() {
+                    Response::Cancelled
+                } else {
+                    let reload_repo = std::mem::take(&mut repo_stale);
+                    respond(reload(&mut repo, &mut log_graph, args, reload_repo, &options, is_stale))
+                }
+            }
+            Request::Describe {
+                commit_id,
+                description,
+            } => respond(
+                repo.describe(&commit_id, &description)
+                    .map(|notices| {
+                        changed(&repo, format!("Updated the description of {}", short(&commit_id)), notices)
+                    }),
+            ),
+            Request::LoadDescription(commit_id) => respond(
+                repo.commit(&commit_id)
+                    .and_then(|commit| repo.draft_description(&commit))
+                    .map(|text| Response::Description { commit_id, text }),
+            ),
+            Request::MoveBookmark { bookmark, commit_id } => respond(
+                repo.move_bookmark(&bookmark, &commit_id)
+                    .map(|notices| {
+                        changed(&repo, format!("Moved {} to {}", bookmark.as_str(), short(&commit_id)), notices)
+                    }),
+            ),
+            Request::Undo => respond(undo.undo(&mut repo).map(|message| Response::Changed {
+                message,
+                undone: true,
+                operation: repo.operation_id().clone(),
+            })),
+            Request::Redo => respond(undo.redo(&mut repo).map(|message| changed(&repo, message, Vec::new()))),
+            Request::LoadOperations => respond(oplog::load_operations(&repo).map(Response::Operations)),
+            Request::DiffOperation(op_id) => respond(oplog::diff_operation(&repo, &op_id).map(Response::OperationDiff)),
+            Request::RestoreOperation(op_id) => {
+                let description = format!("kahva: restore to operation {}", op_id.hex());
+                respond(
+                    repo.prepare_mutation()
+                        .and_then(|()| {
+                            // the head operation after other processes' operations and the snapshot
+                            let head = repo.operation_id().clone();
+                            repo.restore_operation(&head, &op_id, &description)
+                        })
+                        .map(|notices| {
+                            changed(&repo, format!("Restored to operation {}", &op_id.hex()[..12]), notices)
+                        }),
+                )
+            }
+            Request::SetOperation(op_id) => respond(
+                repo.set_at_operation(op_id.map(|op_id| op_id.hex()))
+                    .map(|()| Response::AtOperation(repo.at_operation().cloned())),
+            ),
+            Request::UpdateStale => respond(
+                repo.update_stale()
+                    .map(|notices| changed(&repo, "Updated the stale working copy".to_owned(), notices)),
+            ),
+            Request::Resolve(revision) => respond(
+                repo.revset_single(&revision)
+                    .map(|commit| Response::Resolved(commit.id().clone())),
+            ),
+        });
+        let response = response.unwrap_or_else(|report| {
+            // the loaded repo may not match what is on disk anymore
+            repo_stale = true;
+            // a modification may have committed an operation before panicking, which undo wouldn't know about
+            if !read_only {
+                undo = UndoState::default();
+            }
+            Response::Error(report)
+        });
+
+        if responses.send(response).is_err() {
+            // the UI is gone
+            break;
+        }
+    }
+}
+
+/// Loads the view, only walking and rendering the new commits if it is `log_graph` with more of them.
+fn reload(
+    repo: &mut Repo,
+    log_graph: &mut Option<LogGraph>,
+    args: &Args,
+    reload_repo: bool,
+    options: &ViewOptions,
+    is_stale: impl Fn() -> bool,
+) -> Result<Response> {
+    // taken while loading, so that it is dropped if loading fails or is abandoned halfway
+    let graph = log_graph.take();
+    if let Some(mut graph) = graph.filter(|graph| !reload_repo && graph.continues_with(repo, options)) {
+        let response = match graph.next_page(repo, options.limit, is_stale)? {
+            Some(page) => {
+                *log_graph = Some(graph);
+                Response::Page(page)
+            }
+            None => Response::Cancelled,
+        };
+        return Ok(response);
+    }
+
+    let settings = match reload_repo && repo.reload()? {
+        true => Some(repo.settings().clone()),
+        false => None,
+    };
+    let mut graph = LogGraph::new(repo, args, options)?;
+    let response = match graph.next_page(repo, options.limit, is_stale)? {
+        Some(view) => {
+            *log_graph = Some(graph);
+            Response::View {
+                view,
+                reloaded: reload_repo,
+                settings,
+            }
+        }
+        None => Response::Cancelled,
+    };
+    Ok(response)
+}
+
+/// Undo and redo move a cursor through the operation log.
+#[derive(Default)]
+struct UndoState {
+    /// The operation whose repo state was restored by the last undo or redo,
+    /// and the operation which restored it.
+    cursor: Option<(OperationId, OperationId)>,
+    /// The undone operations, most recent last.
+    undone: Vec<OperationId>,
+}
+
+impl UndoState {
+    /// The operation whose state the repo is in, if it wasn't changed since the last undo or redo.
+    fn restored(&self, repo: &Repo) -> Option<OperationId> {
+        self.cursor
+            .as_ref()
+            .filter(|(_, head)| head == repo.operation_id())
+            .map(|(restored, _)| restored.clone())
+    }
+
+    fn undo(&mut self, repo: &mut Repo) -> Result<String> {
+        // other processes or a snapshot may have done operations since the last reload,
+        // the latest one is undone then
+        repo.prepare_mutation()?;
+        let current = match self.restored(repo) {
+            Some(restored) => restored,
+            None => {
+                self.undone.clear();
+                repo.operation_id().clone()
+            }
+        };
+        let operation = repo.operation(&current)?;
+        let [parent] = operation.parent_ids() else {
+            bail!("Can't undo operation {}, it doesn't have exactly one parent", current.hex());
+        };
+        let parent = parent.clone();
+        let description = operation.metadata().description.clone();
+
+        let notices = repo.restore_operation(&current, &parent, &format!("kahva: undo operation {}", current.hex()))?;
+        self.undone.push(current);
+        self.cursor = Some((parent, repo.operation_id().clone()));
+        Ok(with_notices(format!("Undid {description}"), notices))
+    }
+
+    fn redo(&mut self, repo: &mut Repo) -> Result<String> {
+        // there is nothing to redo after operations by other processes or a snapshot
+        repo.prepare_mutation()?;
+        let (Some(current), Some(next)) = (self.restored(repo), self.undone.last().cloned()) else {
+            bail!("Nothing to redo");
+        };
+        let description = repo.operation(&next)?.metadata().description.clone();
+
+        let notices = repo.restore_operation(&current, &next, &format!("kahva: redo operation {}", next.hex()))?;
+        self.undone.pop();
+        self.cursor = Some((next, repo.operation_id().clone()));
+        Ok(with_notices(format!("Redid {description}"), notices))
+    }
+}
+
+fn changed(repo: &Repo, message: String, notices: Vec<String>) -> Response {
+    Response::Changed {
+        message: with_notices(message, notices),
+        undone: false,
+        operation: repo.operation_id().clone(),
+    }
+}
+
+/// Appends what else happened to the working copies to the message of an operation.
+fn with_notices(mut message: String, notices: Vec<String>) -> String {
+    for notice in notices {
+        message.push('\n');
+        message.push_str(&notice);
+    }
+    message
+}
+
+fn short(commit_id: &CommitId) -> String {
+    commit_id.hex()[..12].to_owned()
+}
+
+fn respond(result: Result<Response>) -> Response {
+    result.unwrap_or_else(|e| Response::Error(e.into()))
+}